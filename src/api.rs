@@ -0,0 +1,73 @@
+//! Entry points for other mods and external tools that want to draw through the overlay.
+//!
+//! The drawing functions only push onto the lock-free draw queue, so they are safe to call from
+//! any thread at any time. [`recent_text`] is as well, see its documentation.
+
+use eldenring::cs::EzDrawTextCoordMode;
+
+use crate::command::DrawCommand;
+use crate::config;
+use crate::inspector::{self, CapturedText};
+use crate::{filter_captured, pool, push_command, u16_ptr_to_string};
+
+/// Queues `text` at (`x`, `y`, `z`) in coordinate `mode` for the next frame, exactly as if the
+/// game had drawn it through EzDraw.
+///
+/// Suppress patterns and substitutions apply, and colour and font size follow the game's
+/// current EzDraw state.
+pub fn draw_text(text: &str, x: f32, y: f32, z: f32, mode: EzDrawTextCoordMode) {
+    let mut text_str = pool::take();
+    text_str.push_str(text);
+    let Some(text_str) = filter_captured(text_str, &config::current()) else {
+        return;
+    };
+
+    push_command(DrawCommand::Text(text_str, x, y, z, mode.into(), None));
+}
+
+/// Queues a line from `from` to `to` in coordinate `mode` for the next frame.
+///
+/// The colour follows the game's current EzDraw text colour.
+pub fn draw_line(from: (f32, f32, f32), to: (f32, f32, f32), mode: EzDrawTextCoordMode) {
+    push_command(DrawCommand::Line(mode.into(), from, to));
+}
+
+/// Queues a rectangle between the corners `from` and `to` in coordinate `mode` for the next
+/// frame, filled or just outlined.
+///
+/// The colour follows the game's current EzDraw text colour.
+pub fn draw_rect(
+    from: (f32, f32, f32),
+    to: (f32, f32, f32),
+    mode: EzDrawTextCoordMode,
+    filled: bool,
+) {
+    push_command(DrawCommand::Rect(mode.into(), from, to, filled));
+}
+
+/// Up to `max` of the strings the overlay drew most recently, oldest first, along with their
+/// coordinate modes and positions. The same history is shown in the F7 panel.
+///
+/// Safe to call from any thread. The history sits behind a mutex the render thread takes once
+/// per frame to append to it, so a call briefly contends with rendering but never blocks it
+/// for longer than copying the result out.
+pub fn recent_text(max: usize) -> Vec<CapturedText> {
+    inspector::recent(max)
+}
+
+/// Queues `text` to be drawn verbatim at ImGui screen pixel (`x`, `y`).
+///
+/// No coordinate mode, resolution scaling or offset is applied, so the text ends up
+/// exactly where the caller asked. Colour and font size follow the game's current EzDraw state.
+///
+/// # Safety
+/// `text` must be null or point to a null-terminated UTF-16 string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn textrender_draw_screen(text: *const u16, x: f32, y: f32) {
+    if text.is_null() {
+        return;
+    }
+
+    let text_str = u16_ptr_to_string(text);
+    push_command(DrawCommand::ScreenText(text_str, x, y));
+}
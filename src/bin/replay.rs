@@ -0,0 +1,109 @@
+//! Replays a draw command recording (`TEXTRENDER_RECORD_PATH`) through the render pipeline
+//! and prints where every label would end up, without needing the game.
+//!
+//! Usage: `textrender-replay [--config <textrender.toml>] <recording.jsonl>`
+//!
+//! Without `--config` the pipeline runs with the default settings, plus any `TEXTRENDER_*`
+//! environment variables.
+
+use std::{
+    fs::File,
+    io::{BufRead, BufReader},
+    path::Path,
+    process::ExitCode,
+};
+
+use textrender::{config, pipeline::Pipeline, recording::RecordedFrame};
+
+const USAGE: &str = "usage: textrender-replay [--config <textrender.toml>] <recording.jsonl>";
+
+fn main() -> ExitCode {
+    let mut config_path = None;
+    let mut path = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--config" => {
+                let Some(value) = args.next() else {
+                    eprintln!("{USAGE}");
+                    return ExitCode::FAILURE;
+                };
+                config_path = Some(value);
+            }
+            _ if path.is_none() => path = Some(arg),
+            _ => {
+                eprintln!("{USAGE}");
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    let Some(path) = path else {
+        eprintln!("{USAGE}");
+        return ExitCode::FAILURE;
+    };
+
+    if let Some(config_path) = config_path {
+        let config_path = Path::new(&config_path);
+        if !config_path.is_file() {
+            eprintln!("config file {} not found", config_path.display());
+            return ExitCode::FAILURE;
+        }
+        // So a file that fails to parse says why rather than silently replaying with defaults
+        let subscriber = tracing_subscriber::fmt()
+            .with_writer(std::io::stderr)
+            .finish();
+        tracing::subscriber::with_default(subscriber, || config::load(config_path));
+    }
+
+    let file = match File::open(&path) {
+        Ok(file) => file,
+        Err(e) => {
+            eprintln!("failed to open {path}: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let config = config::current();
+    let mut pipeline = Pipeline::default();
+
+    for (index, line) in BufReader::new(file).lines().enumerate() {
+        let line = match line {
+            Ok(line) => line,
+            Err(e) => {
+                eprintln!("failed to read {path}: {e}");
+                return ExitCode::FAILURE;
+            }
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let frame = match RecordedFrame::parse(&line) {
+            Ok(frame) => frame,
+            Err(e) => {
+                eprintln!("line {}: {e}", index + 1);
+                continue;
+            }
+        };
+
+        let labels = pipeline.process_commands(frame.commands, &frame.context, &config);
+        // Recordings from before frames were numbered fall back to the line
+        let number = frame.frame.unwrap_or(index as u64);
+        println!("frame {} ({} labels)", number, labels.len());
+        for label in labels {
+            let mode = label
+                .mode
+                .map_or_else(|| String::from("Screen"), |mode| format!("{mode:?}"));
+            let (x, y, z) = label.raw_pos;
+            let (screen_x, screen_y) = label.screen_pos;
+            let [r, g, b, a] = label.color;
+            println!(
+                "  {mode} ({x}, {y}, {z}) -> ({screen_x:.1}, {screen_y:.1}) \
+                 rgba({r:.2}, {g:.2}, {b:.2}, {a:.2}) size {} {:?}",
+                label.font_size, label.text
+            );
+        }
+    }
+
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,183 @@
+use std::fmt::Write;
+
+use hudhook::imgui::{self, Ui};
+use nalgebra::Vector3;
+
+use crate::command::{CoordMode, DrawCommand};
+use crate::config::Config;
+use crate::pipeline::{
+    FrameContext, Pipeline, ResolvedShape, ResolvedText, ScreenOrigin, ShapeKind,
+};
+
+const TOGGLE_KEY: imgui::Key = imgui::Key::F5;
+const COLOR: [f32; 4] = [1.0, 1.0, 0.0, 1.0];
+/// Half the length of the cross marking where a point is expected, in pixels.
+const MARKER_SIZE: f32 = 8.0;
+/// Screen fractions of the screen-space reference points: inset corners, so their labels
+/// stay on screen, and the centre.
+const SCREEN_POINTS: [(&str, f32, f32); 5] = [
+    ("top left", 0.125, 0.125),
+    ("top right", 0.875, 0.125),
+    ("bottom left", 0.125, 0.875),
+    ("bottom right", 0.875, 0.875),
+    ("centre", 0.5, 0.5),
+];
+/// Screen pixel offset the screen-mode points are drawn with, so their labels don't cover those
+/// of the 1080p points at the same spot.
+const SCREEN_MODE_OFFSET: (f32, f32) = (0.0, 64.0);
+/// How far in front of the camera the world-space reference points are, in metres.
+const WORLD_DEPTH: f32 = 10.0;
+/// Offsets of the world-space reference points along the camera's right and up axes, in metres.
+const WORLD_POINTS: [(&str, f32, f32); 3] =
+    [("ahead", 0.0, 0.0), ("right", 3.0, 0.0), ("up", 0.0, 2.0)];
+
+/// Reference points with known screen positions, to check the overlay's scaling and projection
+/// on a particular display.
+///
+/// Each point is submitted as a synthetic draw command and resolved like the game's own text.
+/// A cross marks where it should land and its label, drawn where it did land, lists both.
+/// Toggled with F5.
+pub struct CalibrationGrid {
+    enabled: bool,
+}
+
+impl CalibrationGrid {
+    pub fn new() -> Self {
+        Self { enabled: false }
+    }
+
+    pub fn handle_hotkey(&mut self, ui: &Ui) {
+        if !ui.is_key_pressed(TOGGLE_KEY) {
+            return;
+        }
+
+        self.enabled = !self.enabled;
+        tracing::info!(
+            "Calibration grid {}",
+            if self.enabled { "enabled" } else { "disabled" }
+        );
+    }
+
+    /// Resolves the reference points through `pipeline` and adds their labels and markers.
+    pub fn resolve(
+        &self,
+        pipeline: &mut Pipeline,
+        frame: &FrameContext,
+        config: &Config,
+        labels: &mut Vec<ResolvedText>,
+        shapes: &mut Vec<ResolvedShape>,
+    ) {
+        if !self.enabled {
+            return;
+        }
+
+        for (name, command, expected) in reference_points(frame, config.screen_origin) {
+            shapes.extend(marker(expected));
+            let Some(mut label) = pipeline.process(command, frame, config) else {
+                continue;
+            };
+
+            let (actual_x, actual_y) = label.screen_pos;
+            label.text.clear();
+            let _ = write!(
+                label.text,
+                "{name}\nexpected {:.0}, {:.0}\nactual {actual_x:.0}, {actual_y:.0}",
+                expected.0, expected.1
+            );
+            label.color = COLOR;
+            labels.push(label);
+        }
+    }
+}
+
+/// Commands drawing each reference point, named, with the pixel it should land on.
+///
+/// Screen-space points are submitted measured from `origin`.
+fn reference_points(
+    frame: &FrameContext,
+    origin: ScreenOrigin,
+) -> Vec<(String, DrawCommand, (f32, f32))> {
+    let (width, height) = frame.window_size;
+    let (resolution_x, resolution_y) = frame.window_resolution;
+    let mut points = Vec::new();
+
+    for (name, x, expected_y) in SCREEN_POINTS {
+        let expected = (x * width, expected_y * height);
+        let y = match origin {
+            ScreenOrigin::TopLeft => expected_y,
+            ScreenOrigin::BottomLeft => 1.0 - expected_y,
+        };
+        points.push((
+            format!("1080p {name}"),
+            DrawCommand::Text(
+                String::new(),
+                x * 1920.0,
+                y * 1080.0,
+                0.0,
+                CoordMode::Normalized1080p,
+                None,
+            ),
+            expected,
+        ));
+        points.push((
+            format!("screen {name}"),
+            DrawCommand::Text(
+                String::new(),
+                x * resolution_x,
+                y * resolution_y,
+                0.0,
+                CoordMode::ScreenSpace0,
+                Some(SCREEN_MODE_OFFSET),
+            ),
+            (
+                expected.0 + SCREEN_MODE_OFFSET.0,
+                expected.1 + SCREEN_MODE_OFFSET.1,
+            ),
+        ));
+    }
+
+    if let Some(camera) = &frame.camera {
+        // Worked out from the offsets the points are placed at, independently of the camera's
+        // basis vectors
+        let tan_half_fov = (0.5 * camera.fov).tan();
+        let aspect_ratio = width / height;
+        for (name, right, up) in WORLD_POINTS {
+            let pos: Vector3<f32> = camera.position
+                + camera.forward * WORLD_DEPTH
+                + camera.right * right
+                + camera.up * up;
+            let expected = (
+                0.5 * width * (1.0 + right / (WORLD_DEPTH * tan_half_fov * aspect_ratio)),
+                0.5 * height * (1.0 - up / (WORLD_DEPTH * tan_half_fov)),
+            );
+            points.push((
+                format!("world {name}"),
+                DrawCommand::Text(
+                    String::new(),
+                    pos.x,
+                    pos.y,
+                    pos.z,
+                    CoordMode::HavokPosition3,
+                    None,
+                ),
+                expected,
+            ));
+        }
+    }
+
+    points
+}
+
+/// A cross centred on `(x, y)`.
+fn marker((x, y): (f32, f32)) -> [ResolvedShape; 2] {
+    let line = |from, to| ResolvedShape {
+        kind: ShapeKind::Line,
+        from,
+        to,
+        color: COLOR,
+    };
+    [
+        line((x - MARKER_SIZE, y), (x + MARKER_SIZE, y)),
+        line((x, y - MARKER_SIZE), (x, y + MARKER_SIZE)),
+    ]
+}
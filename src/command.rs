@@ -0,0 +1,85 @@
+use std::time::Instant;
+
+use eldenring::cs::EzDrawTextCoordMode;
+use serde::{Deserialize, Serialize};
+
+/// A request captured from the game (or an external caller) waiting to be drawn.
+#[derive(Debug, Serialize, Deserialize)]
+pub enum DrawCommand {
+    /// Text at a position in a coordinate mode, shifted by the screen pixel offset
+    /// `DrawTextWithOffset` passes along with it.
+    Text(String, f32, f32, f32, CoordMode, Option<(f32, f32)>),
+    /// Text at a final ImGui screen pixel, drawn without any coordinate mode or scaling.
+    ScreenText(String, f32, f32),
+    /// Line between two positions in a coordinate mode.
+    Line(CoordMode, (f32, f32, f32), (f32, f32, f32)),
+    /// Axis-aligned rectangle between two corners in a coordinate mode, filled if the flag is set.
+    Rect(CoordMode, (f32, f32, f32), (f32, f32, f32), bool),
+}
+
+impl DrawCommand {
+    /// The text the command draws, e.g. to recycle its buffer once it's been dropped.
+    /// Empty for shapes.
+    pub fn into_text(self) -> String {
+        match self {
+            Self::Text(text, ..) | Self::ScreenText(text, ..) => text,
+            Self::Line(..) | Self::Rect(..) => String::new(),
+        }
+    }
+}
+
+/// A command waiting in the queue, stamped with when it was captured.
+#[derive(Debug)]
+pub struct QueuedCommand {
+    pub command: DrawCommand,
+    pub queued_at: Instant,
+}
+
+impl From<DrawCommand> for QueuedCommand {
+    fn from(command: DrawCommand) -> Self {
+        Self {
+            command,
+            queued_at: Instant::now(),
+        }
+    }
+}
+
+/// Mirror of [`EzDrawTextCoordMode`] that can be hashed, compared and serialized.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum CoordMode {
+    HavokPosition2,
+    HavokPosition3,
+    ScreenSpace0,
+    ScreenSpace1,
+    Normalized4k,
+    Normalized1080p,
+}
+
+impl CoordMode {
+    pub const ALL: [Self; 6] = [
+        Self::HavokPosition2,
+        Self::HavokPosition3,
+        Self::ScreenSpace0,
+        Self::ScreenSpace1,
+        Self::Normalized4k,
+        Self::Normalized1080p,
+    ];
+
+    /// Whether positions in this mode are Havok world coordinates projected through the camera.
+    pub fn is_world_space(self) -> bool {
+        matches!(self, Self::HavokPosition2 | Self::HavokPosition3)
+    }
+}
+
+impl From<EzDrawTextCoordMode> for CoordMode {
+    fn from(mode: EzDrawTextCoordMode) -> Self {
+        match mode {
+            EzDrawTextCoordMode::HavokPosition2 => Self::HavokPosition2,
+            EzDrawTextCoordMode::HavokPosition3 => Self::HavokPosition3,
+            EzDrawTextCoordMode::ScreenSpace0 => Self::ScreenSpace0,
+            EzDrawTextCoordMode::ScreenSpace1 => Self::ScreenSpace1,
+            EzDrawTextCoordMode::Normalized4k => Self::Normalized4k,
+            EzDrawTextCoordMode::Normalized1080p => Self::Normalized1080p,
+        }
+    }
+}
@@ -0,0 +1,1081 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{
+        Arc, LazyLock, RwLock,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
+};
+
+use hudhook::imgui;
+use serde::{Deserialize, Deserializer};
+
+use crate::command::CoordMode;
+use crate::fonts::FallbackFont;
+use crate::layout::TextAlign;
+use crate::pipeline::ScreenOrigin;
+use crate::projection::BehindCamera;
+use crate::style::Emphasis;
+use crate::text::{self, Substitutions, TextCase, TextPatterns};
+
+static CONFIG: LazyLock<RwLock<Arc<Config>>> =
+    LazyLock::new(|| RwLock::new(Arc::new(Config::default().with_env_overrides())));
+
+/// Name of the config file looked for next to the DLL.
+pub const FILE_NAME: &str = "textrender.toml";
+/// Range [`Config::text_scale`] is clamped to.
+pub const MIN_TEXT_SCALE: f32 = 0.25;
+pub const MAX_TEXT_SCALE: f32 = 4.0;
+/// Range of font oversampling ImGui supports.
+pub const MIN_OVERSAMPLE: i32 = 1;
+pub const MAX_OVERSAMPLE: i32 = 8;
+/// How often [`watch`] checks the config file for changes.
+const WATCH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Runtime settings for the overlay.
+///
+/// Readers grab a cheap snapshot through [`current`], so a settings change never tears
+/// halfway through a frame.
+///
+/// Loaded from [`FILE_NAME`], with `TEXTRENDER_*` environment variables overriding the file.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// What the overlay does while the game is loading.
+    ///
+    /// Off by default: loads are told apart by the player character being missing, which is
+    /// also the case on the title screen and main menu.
+    pub while_loading: InactiveBehavior,
+    /// What the overlay does while the game window isn't the foreground window.
+    pub while_unfocused: InactiveBehavior,
+    /// Longest string, in characters, that is drawn before being truncated. 0 disables the cap.
+    pub max_text_chars: usize,
+    /// Most strings drawn per frame, counting those still lingering after the game stopped
+    /// drawing them. 0 disables the cap.
+    pub max_labels_per_frame: usize,
+    /// Captured text matching any of these regexes is dropped before it is queued.
+    pub suppress_patterns: TextPatterns,
+    /// Rewrites captured text, e.g. to translate it, before it is queued.
+    pub substitutions: Substitutions,
+    pub text_case: TextCase,
+    /// Unicode NFC-normalize captured text before drawing it.
+    pub normalize_nfc: bool,
+    /// Append every frame's draw commands to this JSONL file for offline replay.
+    pub record_path: Option<PathBuf>,
+    /// Append every resolved label to this text file, rotating it once it grows too large.
+    pub capture_path: Option<PathBuf>,
+    /// Size in bytes at which the text capture is moved aside to `<capture_path>.1`.
+    pub capture_max_bytes: u64,
+    /// Append every resolved label as a CSV row to this file.
+    pub csv_path: Option<PathBuf>,
+    /// Write a minidump of the process next to the config file when textrender panics.
+    pub crash_dumps: bool,
+    /// Wrap labels wider than this many screen pixels onto several lines. 0 disables wrapping.
+    pub wrap_width_px: f32,
+    /// Join text the game draws as several fragments on one line into a single label.
+    pub merge_fragments: bool,
+    /// How far apart, in pixels, fragments may be and still count as one line.
+    pub merge_tolerance_px: f32,
+    /// Most labels remembered across frames before the least recently seen is evicted.
+    pub retained_label_capacity: usize,
+    /// Fade out labels the game keeps drawing for longer than this many seconds. 0 disables it.
+    pub max_display_secs: f32,
+    /// Keep drawing labels for this many seconds after the game stops drawing them, fading out
+    /// towards the end. 0 disables it.
+    pub hold_secs: f32,
+    /// Seconds over which new labels fade in. 0 shows them at full opacity right away.
+    pub fade_in_secs: f32,
+    /// Seconds over which labels the game stopped drawing fade out, after any `hold_secs`.
+    /// 0 removes them right away.
+    pub fade_out_secs: f32,
+    /// Window size, in pixels, assumed while the game can't report a usable one.
+    pub fallback_resolution: [f32; 2],
+    /// Multiplier applied on top of the game window's DPI scale.
+    pub dpi_scale_multiplier: f32,
+    /// Multiplier on the size of all overlay text and its decorations, on top of the size the
+    /// game asks for.
+    pub text_scale: f32,
+    pub behind_camera: BehindCamera,
+    /// Which of the game's perspective cameras (1 to 4) world-space text is projected with.
+    pub camera_index: u8,
+    /// World-space text farther than this many metres from the camera is skipped. 0 disables
+    /// the limit.
+    pub max_render_distance: f32,
+    /// Move screen-space text that would cross the edge of the screen back inside it.
+    pub keep_on_screen: bool,
+    /// Corner the game's screen-space positions are measured from.
+    pub screen_origin: ScreenOrigin,
+    /// Pixels the game's screen-space text is kept away from the edges of the screen, so text
+    /// drawn right at the edge isn't clipped. World-space text is unaffected.
+    pub screen_edge_padding: f32,
+    /// Show the overlay's frame time and FPS in the top-left corner.
+    pub show_frame_time: bool,
+    /// Show how much text was drawn this frame, the queue's fill level and dropped commands.
+    pub show_queue_stats: bool,
+    /// Keep queueing captured text while the overlay is paused, dropping the oldest once the
+    /// queue is full.
+    pub capture_while_paused: bool,
+    /// Time the phases of the render loop and show their averages in the settings menu.
+    pub profile_render: bool,
+    /// Graphics API the ImGui hooks attach to.
+    pub backend: RenderBackend,
+    /// Most captured commands waiting to be drawn before the oldest are dropped.
+    ///
+    /// Read once when the queue is created, which happens right after the config is first
+    /// loaded. Every slot is allocated up front at roughly 80 bytes each, plus the heap memory
+    /// of the text it holds, so the default costs under 1 MiB.
+    pub queue_capacity: usize,
+    /// Stream intercepted text as JSON lines to clients connecting to this localhost port.
+    pub tcp_port: Option<u16>,
+    /// Stream intercepted text as JSON lines to a client of this named pipe.
+    pub pipe_name: Option<String>,
+    /// Publish intercepted text into a shared memory ring buffer of this name.
+    pub shared_memory_name: Option<String>,
+    /// Let the game draw its own text as well, instead of the overlay replacing it.
+    pub mirror_native_text: bool,
+    /// Hook `CSEzDraw::DrawText`. Only read at startup.
+    pub hook_draw_text: bool,
+    /// Hook `CSEzDraw::DrawTextWithOffset`. Only read at startup.
+    pub hook_draw_text_with_offset: bool,
+    /// Hides and shows the whole overlay.
+    pub toggle_key: Hotkey,
+    /// Saves a PNG of the game window with the overlay into [`Config::screenshot_dir`].
+    pub screenshot_key: Hotkey,
+    /// Folder screenshots are saved to. Screenshots are disabled while unset.
+    pub screenshot_dir: Option<PathBuf>,
+    /// Coordinate modes whose text and shapes are drawn; everything in other modes is skipped.
+    pub enabled_modes: HashSet<CoordMode>,
+    /// Coordinate modes whose text is laid out top to bottom, as Japanese is written vertically.
+    pub vertical_modes: HashSet<CoordMode>,
+    /// Coordinate modes whose text is emboldened by drawing it twice.
+    pub bold_modes: HashSet<CoordMode>,
+    /// Draw numbers with every character as wide as the widest digit, so rapidly changing
+    /// numbers such as damage don't shift sideways.
+    pub tabular_digits: bool,
+    /// Coordinate modes whose text is slanted like italics.
+    pub italic_modes: HashSet<CoordMode>,
+    /// Colours replacing the game's text colour for text in a coordinate mode.
+    pub mode_colors: HashMap<CoordMode, Rgba>,
+    /// Multiplier applied to the opacity of all text.
+    pub text_alpha: f32,
+    pub font: FontSettings,
+    pub align: AlignSettings,
+    pub pixel_snap: PixelSnapSettings,
+    pub outline: OutlineSettings,
+    pub shadow: ShadowSettings,
+    pub background: BackgroundSettings,
+    pub distance_fade: DistanceFadeSettings,
+    pub distance_tint: DistanceTintSettings,
+    pub log: LogSettings,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            while_loading: InactiveBehavior::Show,
+            while_unfocused: InactiveBehavior::Show,
+            max_text_chars: 1024,
+            max_labels_per_frame: 4096,
+            suppress_patterns: TextPatterns::default(),
+            substitutions: Substitutions::default(),
+            text_case: TextCase::None,
+            normalize_nfc: false,
+            record_path: None,
+            capture_path: None,
+            capture_max_bytes: 10 * 1024 * 1024,
+            csv_path: None,
+            crash_dumps: false,
+            wrap_width_px: 0.0,
+            merge_fragments: false,
+            merge_tolerance_px: 2.0,
+            retained_label_capacity: 2048,
+            max_display_secs: 0.0,
+            hold_secs: 0.0,
+            fade_in_secs: 0.0,
+            fade_out_secs: 0.0,
+            fallback_resolution: [1920.0, 1080.0],
+            dpi_scale_multiplier: 1.0,
+            text_scale: 1.0,
+            behind_camera: BehindCamera::Drop,
+            camera_index: 1,
+            max_render_distance: 0.0,
+            keep_on_screen: false,
+            screen_origin: ScreenOrigin::TopLeft,
+            screen_edge_padding: 0.0,
+            show_frame_time: false,
+            show_queue_stats: false,
+            capture_while_paused: true,
+            profile_render: false,
+            backend: RenderBackend::Auto,
+            queue_capacity: 1024 * 10,
+            tcp_port: None,
+            pipe_name: None,
+            shared_memory_name: None,
+            mirror_native_text: false,
+            hook_draw_text: true,
+            hook_draw_text_with_offset: true,
+            toggle_key: Hotkey(imgui::Key::F8),
+            screenshot_key: Hotkey(imgui::Key::PrintScreen),
+            screenshot_dir: None,
+            enabled_modes: HashSet::from(CoordMode::ALL),
+            vertical_modes: HashSet::new(),
+            bold_modes: HashSet::new(),
+            tabular_digits: false,
+            italic_modes: HashSet::new(),
+            mode_colors: HashMap::new(),
+            text_alpha: 1.0,
+            font: FontSettings::default(),
+            align: AlignSettings::default(),
+            pixel_snap: PixelSnapSettings::default(),
+            outline: OutlineSettings::default(),
+            shadow: ShadowSettings::default(),
+            background: BackgroundSettings::default(),
+            distance_fade: DistanceFadeSettings::default(),
+            distance_tint: DistanceTintSettings::default(),
+            log: LogSettings::default(),
+        }
+    }
+}
+
+/// How the font atlas is rasterized. Changing any of these requires an atlas rebuild.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct FontSettings {
+    /// TrueType font (or collection) the atlas is built from.
+    pub path: PathBuf,
+    /// Face to load when `path` is a collection (`.ttc`) of several, counted from 0. Ignored for
+    /// single fonts.
+    pub face_index: u32,
+    /// Fonts merged in after `path`, in order, for the glyphs it lacks.
+    pub fallbacks: Vec<FallbackFont>,
+    /// Add Korean Hangul to the atlas. Off by default since it adds over 11,000 glyphs.
+    pub hangul: bool,
+    /// Add Hebrew and Arabic to the atlas and draw text that is mostly in those scripts
+    /// right to left, anchored at its right end.
+    pub rtl: bool,
+    /// Pixel size glyphs are rasterized at; the game's font size is scaled relative to it.
+    pub size_px: f32,
+    /// Horizontal oversampling, 1 to 8. Higher values give crisper glyphs at fractional positions
+    /// but multiply the atlas width.
+    pub oversample_h: i32,
+    /// Vertical oversampling, 1 to 8, which multiplies the atlas height the same way.
+    pub oversample_v: i32,
+    pub pixel_snap_h: bool,
+}
+
+impl FontSettings {
+    /// [`FontSettings::oversample_h`] and [`FontSettings::oversample_v`] clamped to the range
+    /// ImGui supports.
+    pub fn oversample(&self) -> (i32, i32) {
+        let clamp = |name: &str, value: i32| {
+            let clamped = value.clamp(MIN_OVERSAMPLE, MAX_OVERSAMPLE);
+            if clamped != value {
+                tracing::warn!(
+                    "font.{} {} is outside {}-{}, using {}",
+                    name,
+                    value,
+                    MIN_OVERSAMPLE,
+                    MAX_OVERSAMPLE,
+                    clamped
+                );
+            }
+            clamped
+        };
+        (
+            clamp("oversample_h", self.oversample_h),
+            clamp("oversample_v", self.oversample_v),
+        )
+    }
+}
+
+impl Default for FontSettings {
+    fn default() -> Self {
+        Self {
+            path: PathBuf::from("C:\\Windows\\Fonts\\msgothic.ttc"),
+            face_index: 0,
+            fallbacks: FallbackFont::default_chain(),
+            hangul: false,
+            rtl: false,
+            size_px: crate::BASE_IMGUI_FONT_SIZE_PX,
+            oversample_h: 3,
+            oversample_v: 1,
+            pixel_snap_h: true,
+        }
+    }
+}
+
+/// Which renderer hook to install.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RenderBackend {
+    /// Pick based on the graphics runtime the game loaded.
+    Auto,
+    Dx11,
+    Dx12,
+}
+
+impl FromStr for RenderBackend {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "auto" => Ok(Self::Auto),
+            "dx11" => Ok(Self::Dx11),
+            "dx12" => Ok(Self::Dx12),
+            _ => Err(()),
+        }
+    }
+}
+
+/// What the overlay does while the game is in a state its text can't be trusted in.
+///
+/// Ordered by precedence, for when several such states apply at once.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InactiveBehavior {
+    /// Keep drawing text as it arrives.
+    Show,
+    /// Keep showing the last frame from before, dropping captured text meanwhile.
+    Freeze,
+    /// Draw nothing and drop captured text.
+    Hide,
+}
+
+impl FromStr for InactiveBehavior {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "show" => Ok(Self::Show),
+            "hide" => Ok(Self::Hide),
+            "freeze" => Ok(Self::Freeze),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Horizontal alignment of labels relative to the position the game drew them at.
+#[derive(Debug, Clone, PartialEq, Default, Deserialize)]
+#[serde(default)]
+pub struct AlignSettings {
+    /// Text projected from Havok world positions.
+    pub world: TextAlign,
+    /// Text in every screen-space mode, including text drawn through the external API.
+    pub screen: TextAlign,
+}
+
+impl AlignSettings {
+    /// Alignment of a label in `mode`, where `None` is text submitted directly in screen pixels.
+    pub fn for_mode(&self, mode: Option<CoordMode>) -> TextAlign {
+        match mode {
+            Some(mode) if mode.is_world_space() => self.world,
+            _ => self.screen,
+        }
+    }
+}
+
+/// Whether labels are drawn at whole pixel positions. Snapping stops text shimmering as it
+/// moves by fractions of a pixel, at the cost of moving in visible one pixel steps.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct PixelSnapSettings {
+    /// Text projected from Havok world positions, which moves smoothly with the camera.
+    pub world: bool,
+    /// Text in every screen-space mode, including text drawn through the external API.
+    pub screen: bool,
+}
+
+impl Default for PixelSnapSettings {
+    fn default() -> Self {
+        Self {
+            world: false,
+            screen: true,
+        }
+    }
+}
+
+impl PixelSnapSettings {
+    /// Whether a label in `mode` is snapped, where `None` is text submitted directly in screen
+    /// pixels.
+    pub fn for_mode(&self, mode: Option<CoordMode>) -> bool {
+        match mode {
+            Some(mode) if mode.is_world_space() => self.world,
+            _ => self.screen,
+        }
+    }
+}
+
+/// Outline drawn around every label. A thickness of 0 disables it.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct OutlineSettings {
+    /// Outline width in pixels.
+    pub thickness: f32,
+    pub color: Rgba,
+}
+
+impl Default for OutlineSettings {
+    fn default() -> Self {
+        Self {
+            thickness: 0.0,
+            color: Rgba([0.0, 0.0, 0.0, 1.0]),
+        }
+    }
+}
+
+/// Drop shadow drawn behind every label.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct ShadowSettings {
+    pub enabled: bool,
+    /// Shadow offset in pixels at the atlas font size; scaled along with the text.
+    pub offset: [f32; 2],
+    pub color: Rgba,
+}
+
+impl Default for ShadowSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            offset: [2.0, 2.0],
+            color: Rgba([0.0, 0.0, 0.0, 0.6]),
+        }
+    }
+}
+
+/// Box drawn behind every label.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct BackgroundSettings {
+    pub enabled: bool,
+    /// Space in pixels between the text and the edge of the box, horizontally and vertically.
+    pub padding: [f32; 2],
+    /// Corner radius in pixels.
+    pub rounding: f32,
+    pub color: Rgba,
+}
+
+impl Default for BackgroundSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            padding: [4.0, 2.0],
+            rounding: 4.0,
+            color: Rgba([0.0, 0.0, 0.0, 0.5]),
+        }
+    }
+}
+
+/// Where logs are written and how much. Only read once, when the DLL is loaded.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct LogSettings {
+    /// Most verbose level logged: `trace`, `debug`, `info`, `warn`, `error` or `off`.
+    pub level: String,
+    /// Append logs to this file as well as the console.
+    pub path: Option<PathBuf>,
+    /// Also send logs to an attached debugger through `OutputDebugString`.
+    pub debugger: bool,
+}
+
+impl Default for LogSettings {
+    fn default() -> Self {
+        Self {
+            level: String::from("debug"),
+            path: None,
+            debugger: false,
+        }
+    }
+}
+
+/// Fades world-space text out with its depth in front of the camera.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct DistanceFadeSettings {
+    pub enabled: bool,
+    /// Depth in metres up to which text is fully opaque.
+    pub near: f32,
+    /// Depth in metres from which text is drawn at `min_alpha`.
+    pub far: f32,
+    /// Alpha multiplier for text at or beyond `far`.
+    pub min_alpha: f32,
+}
+
+impl Default for DistanceFadeSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            near: 10.0,
+            far: 100.0,
+            min_alpha: 0.0,
+        }
+    }
+}
+
+impl DistanceFadeSettings {
+    /// Alpha multiplier for text `depth` metres in front of the camera, falling off linearly
+    /// between `near` and `far`. Always 1 while disabled.
+    pub fn alpha(&self, depth: f32) -> f32 {
+        if !self.enabled {
+            return 1.0;
+        }
+
+        let min_alpha = self.min_alpha.clamp(0.0, 1.0);
+        if depth <= self.near {
+            return 1.0;
+        }
+        if depth >= self.far {
+            return min_alpha;
+        }
+
+        let t = (depth - self.near) / (self.far - self.near);
+        1.0 + (min_alpha - 1.0) * t
+    }
+}
+
+/// Colours world-space text by its depth in front of the camera instead of the game's colour.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct DistanceTintSettings {
+    pub enabled: bool,
+    /// Depth in metres up to which text is drawn in `near_color`.
+    pub near: f32,
+    /// Depth in metres from which text is drawn in `far_color`.
+    pub far: f32,
+    pub near_color: Rgba,
+    pub far_color: Rgba,
+}
+
+impl Default for DistanceTintSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            near: 10.0,
+            far: 100.0,
+            near_color: Rgba([0.0, 1.0, 0.0, 1.0]),
+            far_color: Rgba([1.0, 0.0, 0.0, 1.0]),
+        }
+    }
+}
+
+impl DistanceTintSettings {
+    /// Colour for text `depth` metres in front of the camera, blended linearly between
+    /// `near_color` and `far_color` between `near` and `far`. `None` while disabled.
+    pub fn color(&self, depth: f32) -> Option<[f32; 4]> {
+        if !self.enabled {
+            return None;
+        }
+
+        let t = if depth <= self.near {
+            0.0
+        } else if depth >= self.far {
+            1.0
+        } else {
+            (depth - self.near) / (self.far - self.near)
+        };
+        let (near, far) = (self.near_color.0, self.far_color.0);
+        Some(std::array::from_fn(|i| near[i] + (far[i] - near[i]) * t))
+    }
+}
+
+/// Colour with components in [0.0-1.0], written as hex `RRGGBB` or `RRGGBBAA` in settings.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rgba(pub [f32; 4]);
+
+impl FromStr for Rgba {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let hex = s.trim_start_matches('#');
+        if !matches!(hex.len(), 6 | 8) || !hex.is_ascii() {
+            return Err(());
+        }
+
+        let channel = |i: usize| {
+            hex.get(i * 2..i * 2 + 2)
+                .and_then(|byte| u8::from_str_radix(byte, 16).ok())
+                .map(|byte| byte as f32 / 255.0)
+        };
+        let alpha = if hex.len() == 8 {
+            channel(3).ok_or(())?
+        } else {
+            1.0
+        };
+        Ok(Self([
+            channel(0).ok_or(())?,
+            channel(1).ok_or(())?,
+            channel(2).ok_or(())?,
+            alpha,
+        ]))
+    }
+}
+
+impl<'de> Deserialize<'de> for Rgba {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let hex = String::deserialize(deserializer)?;
+        hex.parse().map_err(|_| {
+            serde::de::Error::custom(format!(
+                "invalid colour {hex:?}, expected hex RRGGBB or RRGGBBAA"
+            ))
+        })
+    }
+}
+
+/// A keyboard key, named as in [`imgui::Key`] (e.g. `F8`, `Insert`) in settings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Hotkey(pub imgui::Key);
+
+impl FromStr for Hotkey {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        imgui::Key::VARIANTS
+            .iter()
+            .copied()
+            .find(|key| format!("{key:?}").eq_ignore_ascii_case(s))
+            .map(Self)
+            .ok_or(())
+    }
+}
+
+impl<'de> Deserialize<'de> for Hotkey {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        name.parse()
+            .map_err(|_| serde::de::Error::custom(format!("unknown key {name:?}")))
+    }
+}
+
+impl Config {
+    /// Colour of text in `mode`, where `None` is text submitted directly in screen pixels.
+    ///
+    /// The configured override for the mode if there is one, otherwise `game_color`, with
+    /// [`Config::text_alpha`] applied either way.
+    pub fn text_color(&self, mode: Option<CoordMode>, game_color: [f32; 4]) -> [f32; 4] {
+        let mut color = mode
+            .and_then(|mode| self.mode_colors.get(&mode))
+            .map_or(game_color, |color| color.0);
+        color[3] *= self.text_alpha;
+        color
+    }
+
+    /// Alignment of `text` drawn in `mode`, where right-to-left text is anchored at its right end.
+    pub fn text_align(&self, mode: Option<CoordMode>, text: &str) -> TextAlign {
+        if self.font.rtl && text::is_rtl(text) {
+            TextAlign::Right
+        } else {
+            self.align.for_mode(mode)
+        }
+    }
+
+    /// Synthetic styling of text drawn in `mode`. Text from the external API is never styled.
+    pub fn emphasis(&self, mode: Option<CoordMode>) -> Emphasis {
+        let Some(mode) = mode else {
+            return Emphasis::default();
+        };
+        Emphasis {
+            bold: self.bold_modes.contains(&mode),
+            italic: self.italic_modes.contains(&mode),
+        }
+    }
+
+    /// [`Config::text_scale`] clamped to [`MIN_TEXT_SCALE`]..=[`MAX_TEXT_SCALE`].
+    pub fn text_scale_factor(&self) -> f32 {
+        if self.text_scale.is_finite() {
+            self.text_scale.clamp(MIN_TEXT_SCALE, MAX_TEXT_SCALE)
+        } else {
+            1.0
+        }
+    }
+
+    /// [`Config::max_display_secs`] as a duration, or `None` when the cap is disabled.
+    pub fn max_display_duration(&self) -> Option<Duration> {
+        positive_duration(self.max_display_secs)
+    }
+
+    /// [`Config::hold_secs`] as a duration, or `None` when holding is disabled.
+    pub fn hold_duration(&self) -> Option<Duration> {
+        positive_duration(self.hold_secs)
+    }
+
+    /// [`Config::fade_in_secs`] as a duration, or `None` when fading in is disabled.
+    pub fn fade_in_duration(&self) -> Option<Duration> {
+        positive_duration(self.fade_in_secs)
+    }
+
+    /// [`Config::fade_out_secs`] as a duration, or `None` when fading out is disabled.
+    pub fn fade_out_duration(&self) -> Option<Duration> {
+        positive_duration(self.fade_out_secs)
+    }
+
+    /// Reads the config file at `path`, or `None` if it is missing or malformed.
+    fn from_file(path: &Path) -> Option<Self> {
+        let contents = match std::fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                tracing::info!("No config file at {}, using defaults", path.display());
+                return None;
+            }
+            Err(e) => {
+                tracing::error!(
+                    "Failed to read config file {}: {}. Using defaults",
+                    path.display(),
+                    e
+                );
+                return None;
+            }
+        };
+
+        toml::from_str(&contents)
+            .inspect(|_| tracing::info!("Loaded config file {}", path.display()))
+            .inspect_err(|e| {
+                tracing::error!(
+                    "Failed to parse config file {}: {}. Using defaults",
+                    path.display(),
+                    e
+                )
+            })
+            .ok()
+    }
+
+    fn with_env_overrides(self) -> Self {
+        let mut config = self;
+
+        if let Some(value) = env_value("TEXTRENDER_FONT") {
+            config.font.path = value;
+        }
+        if let Some(value) = env_value::<f32>("TEXTRENDER_FONT_SIZE") {
+            if value.is_finite() && value > 0.0 {
+                config.font.size_px = value;
+            } else {
+                tracing::warn!(
+                    "Ignoring TEXTRENDER_FONT_SIZE {}, it must be a positive number",
+                    value
+                );
+            }
+        }
+        if let Ok(value) = std::env::var("TEXTRENDER_FONT_FALLBACKS") {
+            match FallbackFont::parse_chain(&value) {
+                Some(fallbacks) => config.font.fallbacks = fallbacks,
+                None => tracing::warn!(
+                    "Ignoring invalid value for TEXTRENDER_FONT_FALLBACKS: {:?}",
+                    value
+                ),
+            }
+        }
+        if let Some(value) = env_value("TEXTRENDER_TEXT_ALPHA") {
+            config.text_alpha = value;
+        }
+        if let Some(value) = env_value("TEXTRENDER_ALIGN_WORLD") {
+            config.align.world = value;
+        }
+        if let Some(value) = env_value("TEXTRENDER_ALIGN_SCREEN") {
+            config.align.screen = value;
+        }
+        if let Some(value) = env_value("TEXTRENDER_PIXEL_SNAP_WORLD") {
+            config.pixel_snap.world = value;
+        }
+        if let Some(value) = env_value("TEXTRENDER_PIXEL_SNAP_SCREEN") {
+            config.pixel_snap.screen = value;
+        }
+        if let Some(value) = env_value("TEXTRENDER_FONT_FACE_INDEX") {
+            config.font.face_index = value;
+        }
+        if let Some(value) = env_value("TEXTRENDER_FONT_HANGUL") {
+            config.font.hangul = value;
+        }
+        if let Some(value) = env_value("TEXTRENDER_FONT_RTL") {
+            config.font.rtl = value;
+        }
+        if let Some(value) = env_value("TEXTRENDER_FONT_OVERSAMPLE_H") {
+            config.font.oversample_h = value;
+        }
+        if let Some(value) = env_value("TEXTRENDER_FONT_OVERSAMPLE_V") {
+            config.font.oversample_v = value;
+        }
+        if let Some(value) = env_value("TEXTRENDER_OUTLINE_THICKNESS") {
+            config.outline.thickness = value;
+        }
+        if let Some(value) = env_value("TEXTRENDER_OUTLINE_COLOR") {
+            config.outline.color = value;
+        }
+        if let Some(value) = env_value("TEXTRENDER_SHADOW") {
+            config.shadow.enabled = value;
+        }
+        if let Some(value) = env_value::<f32>("TEXTRENDER_SHADOW_OFFSET") {
+            config.shadow.offset = [value, value];
+        }
+        if let Some(value) = env_value("TEXTRENDER_SHADOW_COLOR") {
+            config.shadow.color = value;
+        }
+        if let Some(value) = env_value("TEXTRENDER_BACKGROUND") {
+            config.background.enabled = value;
+        }
+        if let Some(value) = env_value::<f32>("TEXTRENDER_BACKGROUND_PADDING") {
+            config.background.padding = [value, value];
+        }
+        if let Some(value) = env_value("TEXTRENDER_BACKGROUND_ROUNDING") {
+            config.background.rounding = value;
+        }
+        if let Some(value) = env_value("TEXTRENDER_BACKGROUND_COLOR") {
+            config.background.color = value;
+        }
+        if let Some(value) = env_value("TEXTRENDER_LOG_LEVEL") {
+            config.log.level = value;
+        }
+        if let Some(value) = env_value("TEXTRENDER_LOG_PATH") {
+            config.log.path = Some(value);
+        }
+        if let Some(value) = env_value("TEXTRENDER_LOG_DEBUGGER") {
+            config.log.debugger = value;
+        }
+        if let Some(value) = env_value("TEXTRENDER_DISTANCE_FADE") {
+            config.distance_fade.enabled = value;
+        }
+        if let Some(value) = env_value("TEXTRENDER_DISTANCE_FADE_NEAR") {
+            config.distance_fade.near = value;
+        }
+        if let Some(value) = env_value("TEXTRENDER_DISTANCE_FADE_FAR") {
+            config.distance_fade.far = value;
+        }
+        if let Some(value) = env_value("TEXTRENDER_DISTANCE_FADE_MIN_ALPHA") {
+            config.distance_fade.min_alpha = value;
+        }
+        if let Some(value) = env_value("TEXTRENDER_DISTANCE_TINT") {
+            config.distance_tint.enabled = value;
+        }
+        if let Some(value) = env_value("TEXTRENDER_DISTANCE_TINT_NEAR") {
+            config.distance_tint.near = value;
+        }
+        if let Some(value) = env_value("TEXTRENDER_DISTANCE_TINT_FAR") {
+            config.distance_tint.far = value;
+        }
+        if let Some(value) = env_value("TEXTRENDER_DISTANCE_TINT_NEAR_COLOR") {
+            config.distance_tint.near_color = value;
+        }
+        if let Some(value) = env_value("TEXTRENDER_DISTANCE_TINT_FAR_COLOR") {
+            config.distance_tint.far_color = value;
+        }
+        if let Some(value) = env_value("TEXTRENDER_WHILE_LOADING") {
+            config.while_loading = value;
+        }
+        if let Some(value) = env_value("TEXTRENDER_WHILE_UNFOCUSED") {
+            config.while_unfocused = value;
+        }
+        if let Some(value) = env_value("TEXTRENDER_MAX_LABELS_PER_FRAME") {
+            config.max_labels_per_frame = value;
+        }
+        if let Some(value) = env_value("TEXTRENDER_MAX_TEXT_CHARS") {
+            config.max_text_chars = value;
+        }
+        if let Some(value) = env_value("TEXTRENDER_TEXT_CASE") {
+            config.text_case = value;
+        }
+        if let Some(value) = env_value("TEXTRENDER_NORMALIZE_NFC") {
+            config.normalize_nfc = value;
+        }
+        if let Some(value) = env_value("TEXTRENDER_MIRROR_NATIVE_TEXT") {
+            config.mirror_native_text = value;
+        }
+        if let Some(value) = env_value("TEXTRENDER_HOOK_DRAW_TEXT") {
+            config.hook_draw_text = value;
+        }
+        if let Some(value) = env_value("TEXTRENDER_HOOK_DRAW_TEXT_WITH_OFFSET") {
+            config.hook_draw_text_with_offset = value;
+        }
+        if let Some(value) = env_value("TEXTRENDER_TOGGLE_KEY") {
+            config.toggle_key = value;
+        }
+        if let Some(value) = env_value("TEXTRENDER_SCREENSHOT_KEY") {
+            config.screenshot_key = value;
+        }
+        if let Some(value) = env_value("TEXTRENDER_SCREENSHOT_DIR") {
+            config.screenshot_dir = Some(value);
+        }
+        if let Some(value) = env_value("TEXTRENDER_QUEUE_CAPACITY") {
+            config.queue_capacity = value;
+        }
+        if let Some(value) = env_value("TEXTRENDER_RECORD_PATH") {
+            config.record_path = Some(value);
+        }
+        if let Some(value) = env_value("TEXTRENDER_CAPTURE_PATH") {
+            config.capture_path = Some(value);
+        }
+        if let Some(value) = env_value("TEXTRENDER_CAPTURE_MAX_BYTES") {
+            config.capture_max_bytes = value;
+        }
+        if let Some(value) = env_value("TEXTRENDER_CSV_PATH") {
+            config.csv_path = Some(value);
+        }
+        if let Some(value) = env_value("TEXTRENDER_CRASH_DUMPS") {
+            config.crash_dumps = value;
+        }
+        if let Some(value) = env_value("TEXTRENDER_TCP_PORT") {
+            config.tcp_port = Some(value);
+        }
+        if let Some(value) = env_value("TEXTRENDER_PIPE_NAME") {
+            config.pipe_name = Some(value);
+        }
+        if let Some(value) = env_value("TEXTRENDER_SHARED_MEMORY_NAME") {
+            config.shared_memory_name = Some(value);
+        }
+        if let Some(value) = env_value("TEXTRENDER_WRAP_WIDTH_PX") {
+            config.wrap_width_px = value;
+        }
+        if let Some(value) = env_value("TEXTRENDER_MERGE_FRAGMENTS") {
+            config.merge_fragments = value;
+        }
+        if let Some(value) = env_value("TEXTRENDER_MERGE_TOLERANCE_PX") {
+            config.merge_tolerance_px = value;
+        }
+        if let Some(value) = env_value("TEXTRENDER_RETAINED_LABEL_CAPACITY") {
+            config.retained_label_capacity = value;
+        }
+        if let Some(value) = env_value("TEXTRENDER_MAX_DISPLAY_SECS") {
+            config.max_display_secs = value;
+        }
+        if let Some(value) = env_value("TEXTRENDER_HOLD_SECS") {
+            config.hold_secs = value;
+        }
+        if let Some(value) = env_value("TEXTRENDER_FADE_IN_SECS") {
+            config.fade_in_secs = value;
+        }
+        if let Some(value) = env_value("TEXTRENDER_FADE_OUT_SECS") {
+            config.fade_out_secs = value;
+        }
+        if let Ok(value) = std::env::var("TEXTRENDER_FALLBACK_RESOLUTION") {
+            match parse_resolution(&value) {
+                Some(resolution) => config.fallback_resolution = resolution,
+                None => tracing::warn!(
+                    "Ignoring invalid value for TEXTRENDER_FALLBACK_RESOLUTION: {:?}",
+                    value
+                ),
+            }
+        }
+        if let Some(value) = env_value("TEXTRENDER_DPI_SCALE") {
+            config.dpi_scale_multiplier = value;
+        }
+        if let Some(value) = env_value("TEXTRENDER_TEXT_SCALE") {
+            config.text_scale = value;
+        }
+        if let Some(value) = env_value("TEXTRENDER_BEHIND_CAMERA") {
+            config.behind_camera = value;
+        }
+        if let Some(value) = env_value("TEXTRENDER_MAX_RENDER_DISTANCE") {
+            config.max_render_distance = value;
+        }
+        if let Some(value) = env_value("TEXTRENDER_CAMERA_INDEX") {
+            config.camera_index = value;
+        }
+        if let Some(value) = env_value("TEXTRENDER_KEEP_ON_SCREEN") {
+            config.keep_on_screen = value;
+        }
+        if let Some(value) = env_value("TEXTRENDER_TABULAR_DIGITS") {
+            config.tabular_digits = value;
+        }
+        if let Some(value) = env_value("TEXTRENDER_SCREEN_ORIGIN") {
+            config.screen_origin = value;
+        }
+        if let Some(value) = env_value("TEXTRENDER_SCREEN_EDGE_PADDING") {
+            config.screen_edge_padding = value;
+        }
+        if let Some(value) = env_value("TEXTRENDER_SHOW_FRAME_TIME") {
+            config.show_frame_time = value;
+        }
+        if let Some(value) = env_value("TEXTRENDER_SHOW_QUEUE_STATS") {
+            config.show_queue_stats = value;
+        }
+        if let Some(value) = env_value("TEXTRENDER_CAPTURE_WHILE_PAUSED") {
+            config.capture_while_paused = value;
+        }
+        if let Some(value) = env_value("TEXTRENDER_PROFILE_RENDER") {
+            config.profile_render = value;
+        }
+        if let Some(value) = env_value("TEXTRENDER_BACKEND") {
+            config.backend = value;
+        }
+
+        config
+    }
+}
+
+/// Parses a `WIDTHxHEIGHT` resolution such as `3840x2160`.
+fn parse_resolution(s: &str) -> Option<[f32; 2]> {
+    let (width, height) = s.trim().split_once(['x', 'X'])?;
+    Some([width.trim().parse().ok()?, height.trim().parse().ok()?])
+}
+
+/// `secs` as a duration, or `None` if it isn't a positive number of seconds.
+fn positive_duration(secs: f32) -> Option<Duration> {
+    (secs > 0.0)
+        .then(|| Duration::try_from_secs_f32(secs).ok())
+        .flatten()
+}
+
+/// Reads and parses `TEXTRENDER_*` overrides, logging values that fail to parse.
+fn env_value<T: FromStr>(name: &str) -> Option<T> {
+    let value = std::env::var(name).ok()?;
+    match value.trim().parse() {
+        Ok(parsed) => Some(parsed),
+        Err(_) => {
+            tracing::warn!("Ignoring invalid value for {}: {:?}", name, value);
+            None
+        }
+    }
+}
+
+/// Loads the config file at `path`, applies the environment overrides and publishes the result.
+pub fn load(path: &Path) {
+    let config = Config::from_file(path)
+        .unwrap_or_default()
+        .with_env_overrides();
+    *CONFIG.write().unwrap() = Arc::new(config);
+}
+
+/// Reloads the config file at `path` whenever its modification time changes, until `stop` is set.
+///
+/// A file that is deleted or fails to parse keeps the settings currently in use, so a
+/// half-saved edit doesn't reset everything to defaults.
+pub fn watch(path: &Path, stop: &AtomicBool) {
+    let modified = || std::fs::metadata(path).and_then(|m| m.modified()).ok();
+    let mut last_modified = modified();
+
+    loop {
+        std::thread::sleep(WATCH_INTERVAL);
+        if stop.load(Ordering::Acquire) {
+            return;
+        }
+
+        let current = modified();
+        if current == last_modified {
+            continue;
+        }
+        last_modified = current;
+
+        if let Some(config) = Config::from_file(path) {
+            *CONFIG.write().unwrap() = Arc::new(config.with_env_overrides());
+        }
+    }
+}
+
+/// Snapshot of the active configuration.
+pub fn current() -> Arc<Config> {
+    CONFIG.read().unwrap().clone()
+}
+
+/// Like [`current`], but gives up instead of blocking, e.g. for a panic hook that may run while
+/// the configuration is being replaced.
+pub fn try_current() -> Option<Arc<Config>> {
+    CONFIG.try_read().ok().map(|config| config.clone())
+}
+
+/// Applies `f` to a copy of the active configuration and publishes the result.
+pub fn update(f: impl FnOnce(&mut Config)) {
+    let mut config = CONFIG.write().unwrap();
+    let mut updated = Config::clone(&config);
+    f(&mut updated);
+    *config = Arc::new(updated);
+}
@@ -0,0 +1,133 @@
+//! CSV export of the resolved labels, for pulling into a spreadsheet.
+
+use std::{
+    borrow::Cow,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+use crate::pipeline::ResolvedText;
+
+/// How often [`CsvExport`] flushes its buffer to disk.
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+const HEADER: &str = "timestamp,mode,raw_x,raw_y,raw_z,screen_x,screen_y,text\n";
+
+/// Appends every resolved label to a CSV file.
+///
+/// Rows are buffered and flushed every [`FLUSH_INTERVAL`] rather than per line.
+#[derive(Default)]
+pub struct CsvExport {
+    /// Path the writer was opened for, to notice the config pointing somewhere else.
+    path: Option<PathBuf>,
+    writer: Option<BufWriter<File>>,
+    last_flush: Option<Instant>,
+    /// Set after an I/O error so a broken path doesn't spam the log every frame.
+    failed: bool,
+}
+
+impl CsvExport {
+    /// Appends `labels` to the CSV file at `path`. Does nothing when `path` is `None`.
+    pub fn record(&mut self, path: Option<&Path>, labels: &[ResolvedText]) {
+        if path != self.path.as_deref() {
+            // Dropping the writer flushes whatever the previous file still had buffered
+            self.writer = None;
+            self.path = path.map(Path::to_owned);
+            self.failed = false;
+        }
+        let Some(path) = path else {
+            return;
+        };
+        if self.failed || labels.is_empty() {
+            return;
+        }
+
+        if self.writer.is_none() {
+            match Self::open(path) {
+                Ok(writer) => {
+                    tracing::info!("Exporting rendered text to {}", path.display());
+                    self.writer = Some(writer);
+                    self.last_flush = Some(Instant::now());
+                }
+                Err(e) => {
+                    tracing::error!("Failed to open CSV export {}: {}", path.display(), e);
+                    self.failed = true;
+                    return;
+                }
+            }
+        }
+        let Some(writer) = self.writer.as_mut() else {
+            return;
+        };
+
+        let timestamp = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs_f64();
+        let mut result = Ok(());
+        for label in labels {
+            let mode = label
+                .mode
+                .map_or_else(|| String::from("Screen"), |mode| format!("{mode:?}"));
+            let (raw_x, raw_y, raw_z) = label.raw_pos;
+            let (screen_x, screen_y) = label.screen_pos;
+            result = writeln!(
+                writer,
+                "{timestamp:.3},{mode},{raw_x},{raw_y},{raw_z},{screen_x:.1},{screen_y:.1},{}",
+                csv_field(&label.text)
+            );
+            if result.is_err() {
+                break;
+            }
+        }
+        if result.is_ok()
+            && self
+                .last_flush
+                .is_none_or(|last| last.elapsed() >= FLUSH_INTERVAL)
+        {
+            self.last_flush = Some(Instant::now());
+            result = writer.flush();
+        }
+
+        if let Err(e) = result {
+            tracing::error!("Failed to write CSV export {}: {}", path.display(), e);
+            self.writer = None;
+            self.failed = true;
+        }
+    }
+
+    fn open(path: &Path) -> io::Result<BufWriter<File>> {
+        let file = File::options().create(true).append(true).open(path)?;
+        let is_new = file.metadata()?.len() == 0;
+        let mut writer = BufWriter::new(file);
+        if is_new {
+            writer.write_all(HEADER.as_bytes())?;
+        }
+        Ok(writer)
+    }
+}
+
+/// Quotes `text` as a CSV field if it contains a delimiter, quote or line break.
+fn csv_field(text: &str) -> Cow<'_, str> {
+    if text.contains([',', '"', '\n', '\r']) {
+        Cow::Owned(format!("\"{}\"", text.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fields_with_delimiters_are_quoted() {
+        assert_eq!(csv_field("HP 1234"), "HP 1234");
+        assert!(matches!(csv_field("HP 1234"), Cow::Borrowed(_)));
+        assert_eq!(csv_field("1,234"), "\"1,234\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+        assert_eq!(csv_field("two\r\nlines"), "\"two\r\nlines\"");
+    }
+}
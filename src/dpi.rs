@@ -0,0 +1,45 @@
+use eldenring::cs::CSWindowImp;
+use fromsoftware_shared::FromStatic;
+use windows::Win32::{
+    Graphics::Gdi::{HMONITOR, MONITOR_DEFAULTTONEAREST, MonitorFromWindow},
+    UI::HiDpi::GetDpiForWindow,
+};
+
+/// DPI Windows treats as 100% scaling.
+const BASE_DPI: f32 = 96.0;
+
+/// DPI scale of the game window, cached per monitor.
+pub struct DpiScale {
+    monitor: Option<HMONITOR>,
+    scale: f32,
+}
+
+impl DpiScale {
+    pub fn new() -> Self {
+        Self {
+            monitor: None,
+            scale: 1.0,
+        }
+    }
+
+    /// Returns the scale for the monitor the game window is on, only asking Windows for the DPI
+    /// again once the window has moved to a different monitor.
+    pub fn update(&mut self) -> f32 {
+        let Ok(window) = (unsafe { CSWindowImp::instance() }) else {
+            return self.scale;
+        };
+        let hwnd = window.window_handle;
+
+        let monitor = unsafe { MonitorFromWindow(hwnd, MONITOR_DEFAULTTONEAREST) };
+        if self.monitor != Some(monitor) {
+            self.monitor = Some(monitor);
+
+            let dpi = unsafe { GetDpiForWindow(hwnd) };
+            // 0 means the handle was invalid, so stick with 1:1 rather than collapsing the text
+            self.scale = if dpi == 0 { 1.0 } else { dpi as f32 / BASE_DPI };
+            tracing::info!("Game window DPI is {} (scale {})", dpi, self.scale);
+        }
+
+        self.scale
+    }
+}
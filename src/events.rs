@@ -0,0 +1,259 @@
+//! Streams intercepted text to external tools as newline-delimited JSON.
+//!
+//! Publishing only ever does a non-blocking send into a bounded channel, so a slow or stuck
+//! consumer costs dropped events rather than stalling the detours. A dispatcher thread fans the
+//! events out to the localhost TCP and named pipe transports, whichever are enabled.
+
+use std::{
+    fs::File,
+    io::Write,
+    net::{Ipv4Addr, TcpListener, TcpStream},
+    os::windows::io::FromRawHandle,
+    sync::{
+        Arc, Mutex, OnceLock,
+        mpsc::{self, Receiver, SyncSender},
+    },
+    time::{Duration, SystemTime},
+};
+
+use serde::Serialize;
+use windows::{
+    Win32::{
+        Foundation::ERROR_PIPE_CONNECTED,
+        Storage::FileSystem::PIPE_ACCESS_OUTBOUND,
+        System::Pipes::{ConnectNamedPipe, CreateNamedPipeW, PIPE_TYPE_BYTE, PIPE_WAIT},
+    },
+    core::PCWSTR,
+};
+
+use crate::command::{CoordMode, DrawCommand};
+
+/// Events (or lines, per transport) waiting to be sent before new ones are dropped.
+const CHANNEL_CAPACITY: usize = 1024;
+/// A client that can't take a line within this long is disconnected.
+const CLIENT_WRITE_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Namespace every local named pipe lives in.
+const PIPE_PREFIX: &str = r"\\.\pipe\";
+/// Outbound buffer of the named pipe; writes block once a client falls this far behind.
+const PIPE_BUFFER_SIZE: u32 = 64 * 1024;
+/// How long to wait before creating the pipe again after waiting for a client failed.
+const PIPE_RETRY_DELAY: Duration = Duration::from_secs(1);
+/// Failed waits for a client in a row before the pipe is given up on.
+const MAX_PIPE_CONNECT_FAILURES: u32 = 10;
+
+static SENDER: OnceLock<SyncSender<TextEvent>> = OnceLock::new();
+static SINKS: Mutex<Vec<SyncSender<Arc<[u8]>>>> = Mutex::new(Vec::new());
+
+/// One intercepted string, as serialized to clients.
+#[derive(Debug, Serialize)]
+pub struct TextEvent {
+    pub text: String,
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    /// `None` for text submitted directly in screen pixels.
+    pub mode: Option<CoordMode>,
+    /// Seconds since the Unix epoch.
+    pub ts: f64,
+}
+
+impl TextEvent {
+    /// The event for a text command, or `None` for shapes.
+    fn from_command(command: &DrawCommand) -> Option<Self> {
+        let (text, x, y, z, mode) = match command {
+            DrawCommand::Text(text, x, y, z, mode, _) => (text, *x, *y, *z, Some(*mode)),
+            DrawCommand::ScreenText(text, x, y) => (text, *x, *y, 0.0, None),
+            DrawCommand::Line(..) | DrawCommand::Rect(..) => return None,
+        };
+
+        Some(Self {
+            text: text.clone(),
+            x,
+            y,
+            z,
+            mode,
+            ts: SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs_f64(),
+        })
+    }
+}
+
+/// Hands `command` to the connected tools. Does nothing unless a server was started.
+pub fn publish(command: &DrawCommand) {
+    let Some(sender) = SENDER.get() else {
+        return;
+    };
+
+    let Some(event) = TextEvent::from_command(command) else {
+        return;
+    };
+    // Full means nobody is keeping up, so dropping is the intended outcome
+    let _ = sender.try_send(event);
+}
+
+/// Registers a new transport, returning the serialized lines it should forward.
+///
+/// Each transport gets its own bounded channel, so one stuck client can't hold up the others.
+fn add_sink() -> Receiver<Arc<[u8]>> {
+    let (sender, receiver) = mpsc::sync_channel(CHANNEL_CAPACITY);
+    SINKS.lock().unwrap().push(sender);
+    SENDER.get_or_init(|| {
+        let (sender, receiver) = mpsc::sync_channel(CHANNEL_CAPACITY);
+        std::thread::spawn(move || dispatch(receiver));
+        sender
+    });
+    receiver
+}
+
+/// Serializes each event once and offers it to every transport.
+fn dispatch(receiver: Receiver<TextEvent>) {
+    for event in receiver {
+        let Ok(mut line) = serde_json::to_vec(&event) else {
+            continue;
+        };
+        line.push(b'\n');
+        let line: Arc<[u8]> = line.into();
+
+        for sink in SINKS.lock().unwrap().iter() {
+            let _ = sink.try_send(line.clone());
+        }
+    }
+}
+
+/// Starts listening on localhost `port` and streaming events to every client that connects.
+pub fn start_tcp(port: u16) {
+    let listener = match TcpListener::bind((Ipv4Addr::LOCALHOST, port)) {
+        Ok(listener) => listener,
+        Err(e) => {
+            tracing::error!(
+                "Failed to listen for text event clients on port {}: {}",
+                port,
+                e
+            );
+            return;
+        }
+    };
+
+    let lines = add_sink();
+    let clients = Arc::new(Mutex::new(Vec::new()));
+    std::thread::spawn({
+        let clients = clients.clone();
+        move || write_tcp(lines, clients)
+    });
+    std::thread::spawn(move || accept_tcp(listener, clients));
+    tracing::info!("Streaming text events on 127.0.0.1:{}", port);
+}
+
+fn accept_tcp(listener: TcpListener, clients: Arc<Mutex<Vec<TcpStream>>>) {
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                tracing::warn!("Failed to accept text event client: {}", e);
+                continue;
+            }
+        };
+        if let Err(e) = stream.set_write_timeout(Some(CLIENT_WRITE_TIMEOUT)) {
+            tracing::warn!("Failed to configure text event client: {}", e);
+            continue;
+        }
+
+        tracing::info!("Text event client connected from {:?}", stream.peer_addr());
+        clients.lock().unwrap().push(stream);
+    }
+}
+
+fn write_tcp(lines: Receiver<Arc<[u8]>>, clients: Arc<Mutex<Vec<TcpStream>>>) {
+    for line in lines {
+        clients
+            .lock()
+            .unwrap()
+            .retain_mut(|client| match client.write_all(&line) {
+                Ok(()) => true,
+                Err(e) => {
+                    tracing::info!("Text event client disconnected: {}", e);
+                    false
+                }
+            });
+    }
+}
+
+/// Serves events on the named pipe `name` to one client at a time, waiting for the next
+/// client whenever the current one disconnects.
+///
+/// `name` may be given without the `\\.\pipe\` prefix.
+pub fn start_pipe(name: &str) {
+    let name = if name.starts_with(PIPE_PREFIX) {
+        name.to_owned()
+    } else {
+        format!("{PIPE_PREFIX}{name}")
+    };
+
+    let lines = add_sink();
+    std::thread::spawn(move || serve_pipe(&name, lines));
+}
+
+fn serve_pipe(name: &str, lines: Receiver<Arc<[u8]>>) {
+    let mut wide_name: Vec<u16> = name.encode_utf16().collect();
+    wide_name.push(0);
+    tracing::info!("Streaming text events on {}", name);
+
+    let mut connect_failures = 0;
+    loop {
+        let pipe = unsafe {
+            CreateNamedPipeW(
+                PCWSTR(wide_name.as_ptr()),
+                PIPE_ACCESS_OUTBOUND,
+                PIPE_TYPE_BYTE | PIPE_WAIT,
+                1,
+                PIPE_BUFFER_SIZE,
+                0,
+                0,
+                None,
+            )
+        };
+        if pipe.is_invalid() {
+            tracing::error!(
+                "Failed to create named pipe {}: {:?}",
+                name,
+                windows::core::Error::from_win32()
+            );
+            return;
+        }
+        // Takes ownership, so the handle is closed whichever way this client ends
+        let mut client = unsafe { File::from_raw_handle(pipe.0 as _) };
+
+        if let Err(e) = unsafe { ConnectNamedPipe(pipe, None) } {
+            // The client may have connected between creating the pipe and waiting for it
+            if e.code() != ERROR_PIPE_CONNECTED.to_hresult() {
+                connect_failures += 1;
+                if connect_failures >= MAX_PIPE_CONNECT_FAILURES {
+                    tracing::error!(
+                        "Failed to wait for named pipe client {} times in a row, closing {}: {:?}",
+                        connect_failures,
+                        name,
+                        e
+                    );
+                    return;
+                }
+                tracing::warn!("Failed to wait for named pipe client: {:?}", e);
+                std::thread::sleep(PIPE_RETRY_DELAY);
+                continue;
+            }
+        }
+        connect_failures = 0;
+        tracing::info!("Text event client connected to {}", name);
+
+        // Whatever piled up while nobody was listening is stale by now
+        lines.try_iter().for_each(drop);
+        for line in &lines {
+            if let Err(e) = client.write_all(&line) {
+                tracing::info!("Text event client disconnected from {}: {}", name, e);
+                break;
+            }
+        }
+    }
+}
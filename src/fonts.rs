@@ -0,0 +1,277 @@
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use hudhook::imgui::{self, FontAtlas, FontGlyphRanges};
+use serde::Deserialize;
+
+use crate::config::FontSettings;
+
+const GLYPH_RANGES: &[u32] = &[
+    0x0020, 0x00FF, // Basic Latin + Latin Supplement
+    0x0100, 0x024F, // Latin Extended-A + Latin Extended-B
+    0x0370, 0x03FF, // Greek and Coptic
+    0x0400, 0x04FF, // Cyrillic
+    0x3000, 0x30FF, // Japanese punctuation, Hiragana, Katakana
+    0x31F0, 0x31FF, // Katakana Phonetic Extensions
+    0x3400, 0x4DBF, // CJK Unified Ideographs Extension A
+    0x4E00, 0x9FFF, // CJK Unified Ideographs
+    0xF900, 0xFAFF, // CJK Compatibility Ideographs
+    0xFF00, 0xFFEF, // Halfwidth and Fullwidth Forms
+    0x2500, 0x257F, // Box Drawing
+    0x2580, 0x259F, // Block Elements (includes ■)
+    0x25A0, 0x25FF, // Geometric Shapes (includes ■ specifically)
+    0,
+];
+
+const GREEK_GLYPH_RANGES: &[u32] = &[
+    0x0020, 0x00FF, // Basic Latin + Latin Supplement
+    0x0370, 0x03FF, // Greek and Coptic
+    0,
+];
+
+const HANGUL_GLYPH_RANGES: &[u32] = &[
+    0x0020, 0x00FF, // Basic Latin + Latin Supplement
+    0x1100, 0x11FF, // Hangul Jamo
+    0x3130, 0x318F, // Hangul Compatibility Jamo
+    0xAC00, 0xD7A3, // Hangul Syllables
+    0,
+];
+
+/// Ships with every Windows install and covers all of [`HANGUL_GLYPH_RANGES`].
+const DEFAULT_HANGUL_FONT: &str = "C:\\Windows\\Fonts\\malgun.ttf";
+
+const RTL_GLYPH_RANGES: &[u32] = &[
+    0x0020, 0x00FF, // Basic Latin + Latin Supplement
+    0x0590, 0x05FF, // Hebrew
+    0x0600, 0x06FF, // Arabic
+    0,
+];
+
+/// Ships with every Windows install and covers all of [`RTL_GLYPH_RANGES`].
+const DEFAULT_RTL_FONT: &str = "C:\\Windows\\Fonts\\arial.ttf";
+
+const SYMBOL_GLYPH_RANGES: &[u32] = &[
+    0x2000, 0x206F, // General Punctuation
+    0x2190, 0x21FF, // Arrows
+    0x2200, 0x22FF, // Mathematical Operators
+    0x2600, 0x26FF, // Miscellaneous Symbols
+    0x2700, 0x27BF, // Dingbats
+    0,
+];
+
+/// Named glyph ranges a fallback font contributes to the atlas.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GlyphRangeSet {
+    /// The same ranges as the primary font.
+    #[default]
+    Default,
+    Cyrillic,
+    Greek,
+    Korean,
+    /// Hebrew and Arabic.
+    Rtl,
+    Thai,
+    Vietnamese,
+    Symbols,
+}
+
+impl GlyphRangeSet {
+    fn ranges(self) -> FontGlyphRanges {
+        match self {
+            Self::Default => FontGlyphRanges::from_slice(GLYPH_RANGES),
+            Self::Cyrillic => FontGlyphRanges::cyrillic(),
+            Self::Greek => FontGlyphRanges::from_slice(GREEK_GLYPH_RANGES),
+            Self::Korean => FontGlyphRanges::from_slice(HANGUL_GLYPH_RANGES),
+            Self::Rtl => FontGlyphRanges::from_slice(RTL_GLYPH_RANGES),
+            Self::Thai => FontGlyphRanges::thai(),
+            Self::Vietnamese => FontGlyphRanges::vietnamese(),
+            Self::Symbols => FontGlyphRanges::from_slice(SYMBOL_GLYPH_RANGES),
+        }
+    }
+}
+
+impl FromStr for GlyphRangeSet {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "default" => Ok(Self::Default),
+            "cyrillic" => Ok(Self::Cyrillic),
+            "greek" => Ok(Self::Greek),
+            "korean" => Ok(Self::Korean),
+            "rtl" => Ok(Self::Rtl),
+            "thai" => Ok(Self::Thai),
+            "vietnamese" => Ok(Self::Vietnamese),
+            "symbols" => Ok(Self::Symbols),
+            _ => Err(()),
+        }
+    }
+}
+
+/// A font merged into the atlas after the primary font.
+///
+/// ImGui falls through to it for codepoints in `glyphs` the primary font doesn't have.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct FallbackFont {
+    pub path: PathBuf,
+    #[serde(default)]
+    pub glyphs: GlyphRangeSet,
+}
+
+impl FallbackFont {
+    /// Fonts shipped with every Windows install that cover what msgothic.ttc lacks.
+    pub fn default_chain() -> Vec<Self> {
+        vec![Self {
+            path: PathBuf::from("C:\\Windows\\Fonts\\seguisym.ttf"),
+            glyphs: GlyphRangeSet::Symbols,
+        }]
+    }
+
+    /// Parses a `;`-separated list of `path=glyphs` entries, e.g.
+    /// `C:\Windows\Fonts\arial.ttf=cyrillic;C:\Windows\Fonts\seguisym.ttf=symbols`.
+    /// Entries without `=glyphs` use [`GlyphRangeSet::Default`]. Returns `None` on unknown glyphs.
+    pub fn parse_chain(s: &str) -> Option<Vec<Self>> {
+        s.split(';')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .map(|entry| match entry.rsplit_once('=') {
+                Some((path, glyphs)) => Some(Self {
+                    path: PathBuf::from(path.trim()),
+                    glyphs: glyphs.trim().parse().ok()?,
+                }),
+                None => Some(Self {
+                    path: PathBuf::from(entry),
+                    glyphs: GlyphRangeSet::Default,
+                }),
+            })
+            .collect()
+    }
+}
+
+/// Adds the overlay font and its fallback chain to `fonts` rasterized with `settings`.
+///
+/// With [`FontSettings::hangul`] set, a Korean font is merged in as well unless the chain
+/// already has one for [`GlyphRangeSet::Korean`], and likewise for [`FontSettings::rtl`] and
+/// [`GlyphRangeSet::Rtl`].
+///
+/// Falls back to ImGui's embedded default font if the primary font file can't be read,
+/// so a missing font costs CJK glyphs rather than crashing the game. Fallback fonts that
+/// can't be read are skipped. The atlas still has to be built and uploaded by the caller.
+pub fn add_fonts(fonts: &mut FontAtlas, settings: &FontSettings) {
+    let (oversample_h, oversample_v) = settings.oversample();
+    let config = |glyph_ranges| imgui::FontConfig {
+        size_pixels: settings.size_px,
+        oversample_h,
+        oversample_v,
+        pixel_snap_h: settings.pixel_snap_h,
+        glyph_ranges,
+        ..Default::default()
+    };
+
+    let primary = std::fs::read(&settings.path)
+        .inspect_err(|e| {
+            tracing::error!(
+                "Failed to read font file {}: {}. Falling back to the default ImGui font",
+                settings.path.display(),
+                e
+            )
+        })
+        .ok()
+        .map(|mut font_data| {
+            select_face(&mut font_data, settings.face_index, &settings.path);
+            font_data
+        });
+    let implied = |enabled: bool, glyphs: GlyphRangeSet, path: &str| {
+        (enabled
+            && !settings
+                .fallbacks
+                .iter()
+                .any(|fallback| fallback.glyphs == glyphs))
+        .then(|| FallbackFont {
+            path: PathBuf::from(path),
+            glyphs,
+        })
+    };
+    let hangul = implied(settings.hangul, GlyphRangeSet::Korean, DEFAULT_HANGUL_FONT);
+    let rtl = implied(settings.rtl, GlyphRangeSet::Rtl, DEFAULT_RTL_FONT);
+    let fallbacks: Vec<(Vec<u8>, GlyphRangeSet)> = settings
+        .fallbacks
+        .iter()
+        .chain(&hangul)
+        .chain(&rtl)
+        .filter_map(|fallback| Some((read_fallback(&fallback.path)?, fallback.glyphs)))
+        .collect();
+
+    // Every source after the first is merged into it by ImGui
+    let mut sources = Vec::with_capacity(1 + fallbacks.len());
+    sources.push(match &primary {
+        Some(font_data) => imgui::FontSource::TtfData {
+            data: font_data,
+            size_pixels: settings.size_px,
+            config: Some(config(FontGlyphRanges::from_slice(GLYPH_RANGES))),
+        },
+        None => imgui::FontSource::DefaultFontData {
+            config: Some(config(FontGlyphRanges::from_slice(GLYPH_RANGES))),
+        },
+    });
+    sources.extend(
+        fallbacks
+            .iter()
+            .map(|(font_data, glyphs)| imgui::FontSource::TtfData {
+                data: font_data,
+                size_pixels: settings.size_px,
+                config: Some(config(glyphs.ranges())),
+            }),
+    );
+
+    fonts.add_font(&sources);
+}
+
+/// Makes face `index` of the font collection `font_data` the one ImGui loads.
+///
+/// imgui-rs always loads the first face of a collection, so the collection's table of face
+/// offsets is reordered to put `index` first. Data that isn't a collection is left alone, and
+/// an index the collection doesn't have keeps the first face.
+fn select_face(font_data: &mut [u8], index: u32, path: &Path) {
+    if index == 0 {
+        return;
+    }
+    if font_data.get(..4) != Some(b"ttcf") {
+        tracing::warn!(
+            "font.face_index {} ignored, {} is not a font collection",
+            index,
+            path.display()
+        );
+        return;
+    }
+
+    let read_u32 = |data: &[u8], at: usize| {
+        data.get(at..at + 4)
+            .map(|bytes| u32::from_be_bytes(bytes.try_into().unwrap()))
+    };
+    let faces = read_u32(font_data, 8).unwrap_or(0);
+    // Each offset is a big-endian u32 following the 12 byte header
+    let entry = |face: u32| 12 + face as usize * 4;
+    if index >= faces || read_u32(font_data, entry(index)).is_none() {
+        tracing::warn!(
+            "{} has {} faces, no face {}. Using face 0",
+            path.display(),
+            faces,
+            index
+        );
+        return;
+    }
+
+    let (first, selected) = (entry(0), entry(index));
+    let (head, tail) = font_data.split_at_mut(selected);
+    head[first..first + 4].swap_with_slice(&mut tail[..4]);
+}
+
+fn read_fallback(path: &Path) -> Option<Vec<u8>> {
+    std::fs::read(path)
+        .inspect_err(|e| tracing::warn!("Skipping fallback font {}: {}", path.display(), e))
+        .ok()
+}
@@ -0,0 +1,22 @@
+use eldenring::cs::{CSCamera, CSWindowImp, WorldChrMan};
+use fromsoftware_shared::FromStatic;
+use windows::Win32::UI::WindowsAndMessaging::GetForegroundWindow;
+
+/// Returns true while the game is between areas.
+///
+/// The main player is torn down for the duration of a load and the camera may not exist
+/// yet, so positions and EzDraw buffers can't be trusted until both are back. There is no
+/// player on the title screen and main menu either, so those count as loading too.
+pub fn is_loading() -> bool {
+    let Ok(world_chr_man) = (unsafe { WorldChrMan::instance() }) else {
+        return true;
+    };
+
+    world_chr_man.main_player.is_none() || unsafe { CSCamera::instance() }.is_err()
+}
+
+/// Returns true while the game window is the foreground window, i.e. not alt-tabbed away from.
+pub fn is_focused() -> bool {
+    unsafe { CSWindowImp::instance() }
+        .is_ok_and(|window| unsafe { GetForegroundWindow() } == window.window_handle)
+}
@@ -0,0 +1,88 @@
+use hudhook::imgui::{self, Ui};
+
+const GRID_COLUMNS: usize = 64;
+const GRID_ROWS: usize = 36;
+const TOGGLE_KEY: imgui::Key = imgui::Key::F9;
+
+/// Accumulates where text lands on screen and paints it as a heatmap behind the overlay.
+///
+/// Toggled with F9. Turning it on starts a fresh recording.
+pub struct Heatmap {
+    enabled: bool,
+    cells: Vec<u32>,
+    max_count: u32,
+}
+
+impl Heatmap {
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            cells: vec![0; GRID_COLUMNS * GRID_ROWS],
+            max_count: 0,
+        }
+    }
+
+    pub fn handle_hotkey(&mut self, ui: &Ui) {
+        if !ui.is_key_pressed(TOGGLE_KEY) {
+            return;
+        }
+
+        self.enabled = !self.enabled;
+        if self.enabled {
+            self.cells.fill(0);
+            self.max_count = 0;
+        }
+        tracing::info!(
+            "Text heatmap {}",
+            if self.enabled { "enabled" } else { "disabled" }
+        );
+    }
+
+    /// Records a final screen position of a drawn label.
+    pub fn record(&mut self, screen_pos: (f32, f32), window_size: (f32, f32)) {
+        if !self.enabled || window_size.0 <= 0.0 || window_size.1 <= 0.0 {
+            return;
+        }
+
+        let column = (screen_pos.0 / window_size.0 * GRID_COLUMNS as f32).floor();
+        let row = (screen_pos.1 / window_size.1 * GRID_ROWS as f32).floor();
+        if !(0.0..GRID_COLUMNS as f32).contains(&column) || !(0.0..GRID_ROWS as f32).contains(&row)
+        {
+            return;
+        }
+
+        let cell = &mut self.cells[row as usize * GRID_COLUMNS + column as usize];
+        *cell = cell.saturating_add(1);
+        self.max_count = self.max_count.max(*cell);
+    }
+
+    pub fn draw(&self, ui: &Ui, window_size: (f32, f32)) {
+        if !self.enabled || self.max_count == 0 {
+            return;
+        }
+
+        let cell_width = window_size.0 / GRID_COLUMNS as f32;
+        let cell_height = window_size.1 / GRID_ROWS as f32;
+        // Log scale so a handful of hot spots don't wash out everything else
+        let max_weight = (self.max_count as f32).ln_1p();
+
+        let draw_list = ui.get_background_draw_list();
+        for (index, &count) in self.cells.iter().enumerate() {
+            if count == 0 {
+                continue;
+            }
+
+            let heat = (count as f32).ln_1p() / max_weight;
+            let x = (index % GRID_COLUMNS) as f32 * cell_width;
+            let y = (index / GRID_COLUMNS) as f32 * cell_height;
+            draw_list
+                .add_rect(
+                    [x, y],
+                    [x + cell_width, y + cell_height],
+                    [heat, 0.2, 1.0 - heat, 0.15 + 0.45 * heat],
+                )
+                .filled(true)
+                .build();
+        }
+    }
+}
@@ -0,0 +1,119 @@
+use std::{
+    collections::VecDeque,
+    sync::{LazyLock, Mutex},
+};
+
+use hudhook::imgui::{self, Ui};
+use serde::{Deserialize, Serialize};
+
+use crate::command::CoordMode;
+use crate::pipeline::ResolvedText;
+
+const TOGGLE_KEY: imgui::Key = imgui::Key::F7;
+/// Most recent labels kept for the panel and [`recent`].
+const CAPACITY: usize = 512;
+
+static RECENT: LazyLock<Mutex<VecDeque<CapturedText>>> =
+    LazyLock::new(|| Mutex::new(VecDeque::with_capacity(CAPACITY)));
+
+/// A string the overlay drew, as it came out of the pipeline.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CapturedText {
+    pub text: String,
+    /// `None` for text submitted directly in screen pixels.
+    pub mode: Option<CoordMode>,
+    /// Position the text was drawn at, in its coordinate mode.
+    pub raw_pos: (f32, f32, f32),
+    /// Where it ended up on screen, in pixels.
+    pub screen_pos: (f32, f32),
+}
+
+/// Records this frame's labels as they came out of the pipeline, before merging or wrapping.
+pub fn record(labels: &[ResolvedText]) {
+    if labels.is_empty() {
+        return;
+    }
+
+    let mut recent = RECENT.lock().unwrap();
+    for label in labels.iter().rev().take(CAPACITY).rev() {
+        if recent.len() == CAPACITY {
+            recent.pop_front();
+        }
+        recent.push_back(CapturedText {
+            text: label.text.clone(),
+            mode: label.mode,
+            raw_pos: label.raw_pos,
+            screen_pos: label.screen_pos,
+        });
+    }
+}
+
+/// Up to `max` of the most recently drawn strings, oldest first.
+pub fn recent(max: usize) -> Vec<CapturedText> {
+    let recent = RECENT.lock().unwrap();
+    recent
+        .iter()
+        .skip(recent.len().saturating_sub(max))
+        .cloned()
+        .collect()
+}
+
+/// Window listing the most recently intercepted text, newest first, for working out what the
+/// game draws where.
+///
+/// Toggled with F7.
+pub struct TextInspector {
+    open: bool,
+    filter: String,
+}
+
+impl TextInspector {
+    pub fn new() -> Self {
+        Self {
+            open: false,
+            filter: String::new(),
+        }
+    }
+
+    pub fn handle_hotkey(&mut self, ui: &Ui) {
+        if ui.is_key_pressed(TOGGLE_KEY) {
+            self.open = !self.open;
+        }
+    }
+
+    pub fn draw(&mut self, ui: &Ui) {
+        if !self.open {
+            return;
+        }
+
+        let filter = &mut self.filter;
+        ui.window("Recent Text")
+            .opened(&mut self.open)
+            .size([520.0, 360.0], imgui::Condition::FirstUseEver)
+            .collapsible(false)
+            .build(|| {
+                ui.input_text("Filter", filter).build();
+                ui.separator();
+
+                let recent = RECENT.lock().unwrap();
+                ui.child_window("entries").build(|| {
+                    for entry in recent
+                        .iter()
+                        .rev()
+                        .filter(|entry| entry.text.contains(filter.as_str()))
+                    {
+                        let mode = match entry.mode {
+                            Some(mode) => format!("{mode:?}"),
+                            None => "API".to_owned(),
+                        };
+                        let (x, y, z) = entry.raw_pos;
+                        let (screen_x, screen_y) = entry.screen_pos;
+                        ui.text(format!(
+                            "[{mode}] ({x:.2}, {y:.2}, {z:.2}) -> ({screen_x:.0}, {screen_y:.0}) {}",
+                            entry.text
+                        ));
+                    }
+                });
+            });
+    }
+}
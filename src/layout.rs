@@ -0,0 +1,419 @@
+//! Measuring and positioning label text.
+
+use std::str::FromStr;
+
+use hudhook::imgui::Ui;
+use serde::Deserialize;
+
+use crate::text;
+
+/// Which end of a line sits at the position the game drew the text at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TextAlign {
+    #[default]
+    Left,
+    Center,
+    Right,
+}
+
+impl TextAlign {
+    /// Horizontal shift that aligns a line `width` pixels wide.
+    pub fn offset(self, width: f32) -> f32 {
+        match self {
+            Self::Left => 0.0,
+            Self::Center => -width * 0.5,
+            Self::Right => -width,
+        }
+    }
+}
+
+impl FromStr for TextAlign {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "left" => Ok(Self::Left),
+            "center" => Ok(Self::Center),
+            "right" => Ok(Self::Right),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Size in screen pixels of `text` drawn at window font scale `font_scale` with the current font.
+///
+/// Independent of the font scale of the window it's called in. Glyph advances come from the
+/// same atlas the text is drawn with, so oversampling and pixel snapping are already reflected
+/// in the result.
+pub fn measure_text(ui: &Ui, text: &str, font_scale: f32) -> [f32; 2] {
+    let [width, height] = ui.calc_text_size(text);
+    // calc_text_size measures at the current window's font size, not the atlas size
+    let scale = font_scale * ui.current_font().font_size / ui.current_font_size();
+    [width * scale, height * scale]
+}
+
+/// Like [`measure_text`], but numeric `text` is measured with [tabular digits](digit_width)
+/// when `tabular` is set.
+pub fn measure_line(ui: &Ui, text: &str, font_scale: f32, tabular: bool) -> [f32; 2] {
+    let height = measure_text(ui, "", font_scale)[1];
+    let width = line_width(text, tabular, |text| measure_text(ui, text, font_scale)[0]);
+    [width, height]
+}
+
+/// Width of the widest digit at `font_scale`, which every character of numeric text is given
+/// with tabular digits so numbers don't change width as their digits change.
+pub fn digit_width(ui: &Ui, font_scale: f32) -> f32 {
+    widest_digit(|digit| measure_text(ui, digit, font_scale)[0])
+}
+
+fn widest_digit(measure: impl Fn(&str) -> f32) -> f32 {
+    const DIGITS: [&str; 10] = ["0", "1", "2", "3", "4", "5", "6", "7", "8", "9"];
+    DIGITS
+        .iter()
+        .map(|&digit| measure(digit))
+        .fold(0.0, f32::max)
+}
+
+/// Width of the line `text`, given the width `measure` returns for any string. With `tabular`
+/// set, numeric text is given the width of the widest digit per character.
+fn line_width(text: &str, tabular: bool, measure: impl Fn(&str) -> f32) -> f32 {
+    if tabular && is_numeric(text) {
+        text.chars().count() as f32 * widest_digit(measure)
+    } else {
+        measure(text)
+    }
+}
+
+/// Whether `text` is a number such as a damage number: digits, optionally with a sign and
+/// separators.
+pub fn is_numeric(text: &str) -> bool {
+    text.chars().any(|c| c.is_ascii_digit())
+        && text
+            .chars()
+            .all(|c| c.is_ascii_digit() || matches!(c, '+' | '-' | '.' | ','))
+}
+
+/// Top-left and bottom-right corners of `text` drawn at `pos` with `align`, in screen pixels.
+///
+/// With `tabular` set, numeric lines are measured as drawn with tabular digits.
+pub fn text_bounds(
+    ui: &Ui,
+    text: &str,
+    font_scale: f32,
+    align: TextAlign,
+    tabular: bool,
+    pos: [f32; 2],
+) -> [[f32; 2]; 2] {
+    if align == TextAlign::Left {
+        let [width, height] = measure_line(ui, text, font_scale, tabular);
+        return [pos, [pos[0] + width, pos[1] + height]];
+    }
+
+    let line_height = measure_text(ui, "", font_scale)[1];
+    let mut left = f32::MAX;
+    let mut right = f32::MIN;
+    let mut lines = 0;
+    for line in text.split('\n') {
+        let width = measure_line(ui, line, font_scale, tabular)[0];
+        let start = pos[0] + align.offset(width);
+        left = left.min(start);
+        right = right.max(start + width);
+        lines += 1;
+    }
+    [[left, pos[1]], [right, pos[1] + lines as f32 * line_height]]
+}
+
+/// Rounds `pos` to whole pixels if `snap` is set, so text doesn't shimmer as it moves by
+/// fractions of a pixel.
+pub fn snap_to_pixels(pos: [f32; 2], snap: bool) -> [f32; 2] {
+    if snap { pos.map(f32::round) } else { pos }
+}
+
+/// Shift that moves the box `bounds` fully inside a screen of `screen_size` pixels, or as far
+/// towards the top-left as possible when it is larger than the screen.
+pub fn shift_onto_screen([min, max]: [[f32; 2]; 2], screen_size: (f32, f32)) -> (f32, f32) {
+    let shift = |min: f32, max: f32, size: f32| {
+        if min < 0.0 {
+            -min
+        } else if max > size {
+            (size - max).max(-min)
+        } else {
+            0.0
+        }
+    };
+    (
+        shift(min[0], max[0], screen_size.0),
+        shift(min[1], max[1], screen_size.1),
+    )
+}
+
+/// Breaks `text` onto several lines so no line is wider than `max_width` screen pixels as
+/// measured by `measure`.
+///
+/// Latin text breaks between words and CJK text between any two characters. Lines the game
+/// already broke with `\n` are wrapped individually, and a word too wide for a line of its own
+/// is broken between characters. Text that already fits is returned as is.
+pub fn wrap_text(text: String, max_width: f32, measure: impl Fn(&str) -> f32) -> String {
+    if text.lines().all(|line| measure(line) <= max_width) {
+        return text;
+    }
+
+    let mut wrapper = LineWrapper {
+        wrapped: String::with_capacity(text.len() + 8),
+        current: String::new(),
+        max_width,
+        measure,
+    };
+    for (index, line) in text.split('\n').enumerate() {
+        if index > 0 {
+            wrapper.break_line();
+        }
+        for token in tokens(line) {
+            wrapper.push(token);
+        }
+    }
+    wrapper.break_line();
+    // break_line always leaves a trailing newline behind
+    wrapper.wrapped.pop();
+    wrapper.wrapped
+}
+
+struct LineWrapper<F> {
+    wrapped: String,
+    /// Line being filled, not yet part of `wrapped`.
+    current: String,
+    max_width: f32,
+    measure: F,
+}
+
+impl<F: Fn(&str) -> f32> LineWrapper<F> {
+    fn fits(&mut self, token: &str) -> bool {
+        let len = self.current.len();
+        self.current.push_str(token);
+        let width = (self.measure)(self.current.trim_end());
+        self.current.truncate(len);
+        width <= self.max_width
+    }
+
+    fn break_line(&mut self) {
+        self.wrapped.push_str(self.current.trim_end());
+        self.wrapped.push('\n');
+        self.current.clear();
+    }
+
+    fn push(&mut self, token: &str) {
+        if self.fits(token) {
+            self.current.push_str(token);
+            return;
+        }
+
+        let token = token.trim_start();
+        if self.current.trim_end().is_empty() {
+            self.current.clear();
+        } else {
+            self.break_line();
+            if self.fits(token) {
+                self.current.push_str(token);
+                return;
+            }
+        }
+
+        // Too wide for a line of its own
+        for cluster in text::clusters(token) {
+            if !self.current.is_empty() && !self.fits(cluster) {
+                self.break_line();
+            }
+            self.current.push_str(cluster);
+        }
+    }
+}
+
+/// A piece of vertically laid out text.
+pub struct VerticalCell<'a> {
+    /// Counted from the right, since vertical Japanese reads its columns right to left.
+    pub column: usize,
+    /// Counted from the top.
+    pub row: usize,
+    pub text: &'a str,
+}
+
+/// Splits `text` into the cells of a vertical layout, one column per line.
+///
+/// CJK characters and emoji each take a cell of their own, along with any marks attached to
+/// them. Runs of anything else, such as Latin words or
+/// numbers, share one and stay horizontal within it. Whitespace between them takes no cell.
+pub fn vertical_cells(text: &str) -> Vec<VerticalCell<'_>> {
+    let mut cells = Vec::new();
+    for (column, line) in text.split('\n').enumerate() {
+        let mut row = 0;
+        let mut rest = line.trim_start();
+        while let Some(first) = text::clusters(rest).next() {
+            let end = if first.chars().next().is_some_and(breaks_anywhere) {
+                first.len()
+            } else {
+                rest.find(|c: char| c.is_whitespace() || breaks_anywhere(c))
+                    .unwrap_or(rest.len())
+            };
+            cells.push(VerticalCell {
+                column,
+                row,
+                text: &rest[..end],
+            });
+            row += 1;
+            rest = rest[end..].trim_start();
+        }
+    }
+    cells
+}
+
+/// Screen space box covered by `cells` laid out from `pos`, the top-left corner of the first
+/// column, with square cells of `cell_size` pixels.
+pub fn vertical_bounds(cells: &[VerticalCell], cell_size: f32, pos: [f32; 2]) -> [[f32; 2]; 2] {
+    let columns = cells.iter().map(|cell| cell.column + 1).max().unwrap_or(1);
+    let rows = cells.iter().map(|cell| cell.row + 1).max().unwrap_or(1);
+    [
+        [pos[0] - (columns - 1) as f32 * cell_size, pos[1]],
+        [pos[0] + cell_size, pos[1] + rows as f32 * cell_size],
+    ]
+}
+
+/// Splits `line` into the pieces it may be broken between: single CJK characters or emoji and
+/// words. Whitespace stays attached to the end of the piece before it.
+fn tokens(line: &str) -> impl Iterator<Item = &str> {
+    let mut rest = line;
+    std::iter::from_fn(move || {
+        let first = text::clusters(rest).next()?;
+
+        let mut end = if first.chars().next().is_some_and(breaks_anywhere) {
+            first.len()
+        } else {
+            rest.char_indices()
+                .skip(1)
+                .find(|&(_, c)| c.is_whitespace() || breaks_anywhere(c))
+                .map_or(rest.len(), |(index, _)| index)
+        };
+        end += rest[end..].len() - rest[end..].trim_start().len();
+
+        let (token, tail) = rest.split_at(end);
+        rest = tail;
+        Some(token)
+    })
+}
+
+/// Whether `c` belongs to a script written without spaces or is an emoji, so lines may break
+/// on either side.
+fn breaks_anywhere(c: char) -> bool {
+    matches!(
+        c,
+        '\u{3000}'..='\u{30FF}' // Japanese punctuation, Hiragana, Katakana
+            | '\u{31F0}'..='\u{31FF}' // Katakana Phonetic Extensions
+            | '\u{3400}'..='\u{4DBF}' // CJK Unified Ideographs Extension A
+            | '\u{4E00}'..='\u{9FFF}' // CJK Unified Ideographs
+            | '\u{AC00}'..='\u{D7AF}' // Hangul Syllables
+            | '\u{F900}'..='\u{FAFF}' // CJK Compatibility Ideographs
+            | '\u{FF00}'..='\u{FFEF}' // Halfwidth and Fullwidth Forms
+            | '\u{1F300}'..='\u{1FAFF}' // Emoji and pictographs
+            | '\u{20000}'..='\u{3FFFF}' // Supplementary and Tertiary Ideographic Planes
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Proportional widths, with `1` much narrower than `8`.
+    fn proportional(text: &str) -> f32 {
+        text.chars()
+            .map(|c| match c {
+                '1' => 4.0,
+                '8' => 10.0,
+                _ => 8.0,
+            })
+            .sum()
+    }
+
+    #[test]
+    fn tabular_digits_measure_equal_widths() {
+        assert_ne!(
+            line_width("-1,111", false, proportional),
+            line_width("+8,888", false, proportional)
+        );
+        assert_eq!(
+            line_width("-1,111", true, proportional),
+            line_width("+8,888", true, proportional)
+        );
+        assert_eq!(line_width("111", true, proportional), 30.0);
+        // Only numbers are laid out in digit cells
+        assert_eq!(line_width("Lv 11", true, proportional), 32.0);
+    }
+
+    /// Every character one pixel wide.
+    fn monospace(text: &str) -> f32 {
+        text.chars().count() as f32
+    }
+
+    #[test]
+    fn wrap_keeps_text_that_fits() {
+        assert_eq!(
+            wrap_text("short\nlines".into(), 10.0, monospace),
+            "short\nlines"
+        );
+    }
+
+    #[test]
+    fn wrap_breaks_latin_between_words() {
+        assert_eq!(
+            wrap_text("the quick brown fox".into(), 10.0, monospace),
+            "the quick\nbrown fox"
+        );
+    }
+
+    #[test]
+    fn wrap_breaks_cjk_between_characters() {
+        assert_eq!(
+            wrap_text("一二三四五".into(), 2.0, monospace),
+            "一二\n三四\n五"
+        );
+    }
+
+    #[test]
+    fn wrap_keeps_existing_line_breaks() {
+        assert_eq!(
+            wrap_text("aaa bbb\nccc".into(), 3.0, monospace),
+            "aaa\nbbb\nccc"
+        );
+    }
+
+    #[test]
+    fn wrap_breaks_words_wider_than_a_line() {
+        assert_eq!(
+            wrap_text("abcdefgh ij".into(), 3.0, monospace),
+            "abc\ndef\ngh\nij"
+        );
+    }
+
+    #[test]
+    fn wrap_breaks_between_supplementary_plane_characters() {
+        assert_eq!(
+            wrap_text("\u{20000}\u{20001}\u{20002}".into(), 2.0, monospace),
+            "\u{20000}\u{20001}\n\u{20002}"
+        );
+    }
+
+    #[test]
+    fn boxes_outside_the_screen_are_shifted_inside() {
+        let screen = (1920.0, 1080.0);
+        for [min, max] in [
+            [[-50.0, -20.0], [50.0, 10.0]],
+            [[1900.0, 1070.0], [2000.0, 1100.0]],
+            [[-300.0, 500.0], [-200.0, 530.0]],
+            [[3000.0, 2000.0], [3100.0, 2030.0]],
+        ] {
+            let (x, y) = shift_onto_screen([min, max], screen);
+            let (min, max) = ([min[0] + x, min[1] + y], [max[0] + x, max[1] + y]);
+            assert!(min[0] >= 0.0 && min[1] >= 0.0, "{min:?}");
+            assert!(max[0] <= screen.0 && max[1] <= screen.1, "{max:?}");
+        }
+    }
+}
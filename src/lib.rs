@@ -1,79 +1,328 @@
+mod api;
+mod calibration;
+pub mod command;
+pub mod config;
+mod csv_export;
+mod dpi;
+mod events;
+mod fonts;
+mod game_state;
+mod heatmap;
+mod inspector;
+pub mod layout;
 mod logging;
+mod metrics;
+pub mod pipeline;
+mod pool;
+pub mod projection;
+pub mod recording;
+mod retained;
+mod screenshot;
+mod settings;
+mod shared_memory;
+mod signatures;
+mod style;
+pub mod text;
+
+pub use api::{draw_line, draw_rect, draw_text, recent_text};
+pub use inspector::CapturedText;
 
 use std::{
-    hash::{Hash, Hasher},
+    ffi::OsString,
+    fmt::Write,
     mem::transmute,
-    sync::LazyLock,
-    time::Duration,
+    os::windows::ffi::OsStringExt,
+    path::{Path, PathBuf},
+    sync::{
+        LazyLock,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
 };
 
 use eldenring::{
-    cs::{CSCamera, CSEzDraw, CSWindowImp, CSWindowType, EzDrawTextCoordMode, RendMan},
+    cs::{CSEzDraw, CSWindowImp, CSWindowType, RendMan},
     util::system::wait_for_system_init,
 };
 use fromsoftware_shared::{F32Vector2, F32Vector4, FromStatic, Program};
-use nalgebra::Vector3;
 
-use crate::logging::{custom_panic_hook, setup_logging};
+use crate::calibration::CalibrationGrid;
+use crate::command::{DrawCommand, QueuedCommand};
+use crate::config::{Config, FontSettings, InactiveBehavior, RenderBackend};
+use crate::csv_export::CsvExport;
+use crate::dpi::DpiScale;
+use crate::heatmap::Heatmap;
+use crate::inspector::TextInspector;
+use crate::layout::TextAlign;
+use crate::logging::{
+    LogThrottle, TextCapture, custom_panic_hook, debug_throttled, hot_span, setup_logging,
+};
+use crate::metrics::{DropReport, FrameProfile, LatencyStats, Phase};
+use crate::pipeline::{FrameContext, Pipeline, ResolvedShape, ResolvedText};
+use crate::projection::CameraBasis;
+use crate::recording::Recorder;
+use crate::retained::RetainedLabels;
+use crate::screenshot::Screenshot;
+use crate::settings::{OverlayStats, SettingsMenu};
+use crate::signatures::GameVersion;
+use crate::style::Emphasis;
 use crossbeam_queue::ArrayQueue;
 use hudhook::{
     Hudhook, ImguiRenderLoop, RenderContext,
     imgui::{self, Ui},
-    windows::Win32::{
-        Foundation::HINSTANCE,
-        System::{LibraryLoader::DisableThreadLibraryCalls, SystemServices::DLL_PROCESS_ATTACH},
+    windows::{
+        Win32::{
+            Foundation::{HINSTANCE, HMODULE},
+            System::{
+                LibraryLoader::{DisableThreadLibraryCalls, GetModuleFileNameW, GetModuleHandleW},
+                SystemServices::{DLL_PROCESS_ATTACH, DLL_PROCESS_DETACH},
+            },
+        },
+        core::{PCWSTR, w},
     },
 };
-use hudhook::{hooks::dx12::ImguiDx12Hooks, imgui::Context};
+use hudhook::{
+    hooks::{dx11::ImguiDx11Hooks, dx12::ImguiDx12Hooks},
+    imgui::Context,
+};
 use pelite::pe::Pe;
 use retour::static_detour;
+use windows::Win32::UI::WindowsAndMessaging::IsIconic;
+
+static TEXT_RENDER_QUEUE: LazyLock<ArrayQueue<QueuedCommand>> =
+    LazyLock::new(|| ArrayQueue::new(config::current().queue_capacity.max(1)));
+
+/// Most debug lines per second from each per-draw log call site.
+const HOT_PATH_LOG_LINES_PER_SEC: u32 = 20;
+static RENDER_LOG: LogThrottle = LogThrottle::new(HOT_PATH_LOG_LINES_PER_SEC);
+static DRAW_TEXT_LOG: LogThrottle = LogThrottle::new(HOT_PATH_LOG_LINES_PER_SEC);
+static DRAW_TEXT_WITH_OFFSET_LOG: LogThrottle = LogThrottle::new(HOT_PATH_LOG_LINES_PER_SEC);
+
+/// Top-left corner of the frame time and queue readouts, in screen pixels.
+const DIAGNOSTICS_POS: (f32, f32) = (8.0, 8.0);
 
-static TEXT_RENDER_QUEUE: LazyLock<ArrayQueue<DrawCommand>> =
-    LazyLock::new(|| ArrayQueue::new(1024 * 10));
+/// Set when the DLL is being unloaded, so background threads stop installing or touching hooks.
+static SHUTTING_DOWN: AtomicBool = AtomicBool::new(false);
+/// Set while the overlay is paused and configured to stop queueing captured text meanwhile.
+static CAPTURE_PAUSED: AtomicBool = AtomicBool::new(false);
+/// Commands that arrived while the queue was full and pushed out the oldest queued command,
+/// plus labels left undrawn because a frame had more than [`Config::max_labels_per_frame`].
+static DROPPED_COMMANDS: AtomicU64 = AtomicU64::new(0);
 
 const BASE_IMGUI_FONT_SIZE_PX: f32 = 24.0;
+/// Rebuilds the font atlas from the current config
+const FONT_RELOAD_KEY: imgui::Key = imgui::Key::F10;
+const QUEUE_STATS_KEY: imgui::Key = imgui::Key::F11;
+const PAUSE_KEY: imgui::Key = imgui::Key::Pause;
+/// Keeps projecting world-space text with the camera as it is now, or goes back to the live one
+const FREEZE_CAMERA_KEY: imgui::Key = imgui::Key::F6;
 
-#[derive(Debug)]
-enum DrawCommand {
-    Text(String, f32, f32, f32, EzDrawTextCoordMode),
-    SetOffset(f32, f32),
+/// Longest captured string read, in UTF-16 units, before giving up on finding its terminator.
+const MAX_TEXT_UNITS: usize = 8192;
+
+/// Anything larger than this read from `CSWindowImp` is garbage, e.g. mid-resize.
+const MAX_WINDOW_DIMENSION: f32 = 16384.0;
+
+/// Falls back to the configured [`Config::fallback_resolution`] when `dimensions` are missing or
+/// not a sane size.
+///
+/// `falling_back` tracks whether the last call fell back, so a resize logs once rather
+/// than every frame.
+fn validated_window_dimensions(
+    dimensions: Option<(f32, f32)>,
+    what: &str,
+    falling_back: &AtomicBool,
+) -> (f32, f32) {
+    let is_sane = |v: f32| v.is_finite() && (1.0..=MAX_WINDOW_DIMENSION).contains(&v);
+
+    match dimensions {
+        Some((width, height)) if is_sane(width) && is_sane(height) => {
+            falling_back.store(false, Ordering::Relaxed);
+            (width, height)
+        }
+        _ => {
+            let [width, height] = config::current().fallback_resolution;
+            let fallback = (width, height);
+            if !falling_back.swap(true, Ordering::Relaxed) {
+                tracing::warn!(
+                    "Invalid {} {:?}, falling back to {:?}",
+                    what,
+                    dimensions,
+                    fallback
+                );
+            }
+            fallback
+        }
+    }
 }
 
+/// Queues `command` for the next frame, evicting the oldest queued command if the queue is full.
+fn push_command(command: DrawCommand) {
+    events::publish(&command);
+    shared_memory::publish(&command);
+    if CAPTURE_PAUSED.load(Ordering::Relaxed) {
+        pool::recycle(command.into_text());
+        return;
+    }
+    if let Err(queued) = TEXT_RENDER_QUEUE.push(command.into()) {
+        DROPPED_COMMANDS.fetch_add(1, Ordering::Relaxed);
+        if let Some(evicted) = TEXT_RENDER_QUEUE.force_push(queued) {
+            pool::recycle(evicted.command.into_text());
+        }
+    }
+}
+
+/// Drops everything waiting in the queue, recycling the text buffers.
+fn clear_queue() {
+    while let Some(queued) = TEXT_RENDER_QUEUE.pop() {
+        pool::recycle(queued.command.into_text());
+    }
+}
+
+/// Applies `config`'s substitutions to captured game text, or returns `None` if it matches one
+/// of the suppress patterns.
+///
+/// Takes the config from the caller, which the detours have already read for the same call.
+fn filter_captured(text: String, config: &Config) -> Option<String> {
+    if config.suppress_patterns.is_match(&text) {
+        pool::recycle(text);
+        return None;
+    }
+    Some(config.substitutions.apply(text))
+}
+
+/// Decodes a null-terminated UTF-16 string into a buffer taken from the [`pool`].
+///
+/// A null `ptr` decodes to an empty string, and a string without a terminator in the first
+/// [`MAX_TEXT_UNITS`] units is cut off there rather than walking off into unrelated memory.
 fn u16_ptr_to_string(ptr: *const u16) -> String {
-    let len = (0..)
-        .take_while(|&i| unsafe { *ptr.offset(i) } != 0)
+    static WARNED_UNTERMINATED: AtomicBool = AtomicBool::new(false);
+
+    if ptr.is_null() {
+        return pool::take();
+    }
+
+    let mut len = (0..MAX_TEXT_UNITS)
+        .take_while(|&i| unsafe { *ptr.add(i) } != 0)
         .count();
+    // The game re-issues the same text every frame, so once is enough to know it happens
+    if len == MAX_TEXT_UNITS && !WARNED_UNTERMINATED.swap(true, Ordering::Relaxed) {
+        tracing::warn!(
+            "Captured text has no terminator within {} UTF-16 units, cutting it off",
+            MAX_TEXT_UNITS
+        );
+    }
+    // Don't cut a surrogate pair in half, which would leave a replacement character behind
+    if len == MAX_TEXT_UNITS && (0xD800..0xDC00).contains(&unsafe { *ptr.add(len - 1) }) {
+        len -= 1;
+    }
     let slice = unsafe { std::slice::from_raw_parts(ptr, len) };
 
-    String::from_utf16(slice).unwrap_or(String::from("?EncodingError?"))
+    // Same as String::from_utf16_lossy, but into a recycled buffer. Unpaired surrogates become
+    // U+FFFD so the rest of the string still shows up
+    let mut text = pool::take();
+    text.extend(
+        char::decode_utf16(slice.iter().copied()).map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER)),
+    );
+    text
 }
 
-// void CS::CSEzDraw::DrawText(CSEzDraw *param_1,FloatVector4 *param_2,wchar_t *param_3)
-const TEXT_RENDER_REQUEST_RVA: u32 = 0x264efc0;
-// void CS::CSEzDraw::DrawTextWithOffset(CSEzDraw *param_1,FloatVector4 *param_2,float (*offset) [2],wchar_t *param_4)
-const DRAW_TEXT_WITH_OFFSET_RVA: u32 = 0x264ef20;
-
 static_detour! {
     static DrawTextRenderRequest: unsafe extern "C" fn(*mut CSEzDraw, *mut F32Vector4, *const u16) -> ();
     static DrawTextWithOffset: unsafe extern "C" fn(*mut CSEzDraw, *mut F32Vector4, *mut F32Vector2, *const u16) -> ();
 }
 
 struct DebugTextRender {
-    offset: (f32, f32),
+    pipeline: Pipeline,
+    recorder: Recorder,
+    capture: TextCapture,
+    csv: CsvExport,
+    latency: LatencyStats,
+    drops: DropReport,
+    profile: FrameProfile,
+    retained: RetainedLabels,
+    /// Scratch buffer for the order labels are drawn in, kept to avoid reallocating every frame.
+    draw_order: Vec<usize>,
+    loading: bool,
+    /// Whether the game window was last seen in the background.
+    unfocused: bool,
+    /// Cleared by the toggle key to hide the overlay while the hooks keep capturing.
+    enabled: bool,
+    /// Set by the pause key to stop draining the queue and keep showing the last frame.
+    paused: bool,
+    /// What was drawn last frame, shown again while paused.
+    last_shapes: Vec<ResolvedShape>,
+    last_labels: Vec<ResolvedText>,
+    /// Camera world-space text is projected with instead of the live one, set by the freeze key.
+    frozen_camera: Option<CameraBasis>,
+    /// Last `text_pos_height_scale` read from the game, to log when it changes.
+    text_height_scale: f32,
+    settings: SettingsMenu,
+    heatmap: Heatmap,
+    calibration: CalibrationGrid,
+    inspector: TextInspector,
+    dpi: DpiScale,
+    /// Settings the current font atlas was built with
+    font: FontSettings,
+    /// Font settings last seen in the config, to notice a reload changing them
+    configured_font: FontSettings,
+    atlas_size: Option<(u32, u32)>,
+    pending_font_rebuild: Option<FontSettings>,
+    /// Folder a screenshot is taken into at the start of the next frame, once the frame the
+    /// screenshot key was pressed in has been presented.
+    pending_screenshot: Option<PathBuf>,
 }
 impl DebugTextRender {
     fn new() -> Self {
-        Self { offset: (0.0, 0.0) }
+        Self {
+            pipeline: Pipeline::default(),
+            recorder: Recorder::default(),
+            capture: TextCapture::default(),
+            csv: CsvExport::default(),
+            latency: LatencyStats::new(),
+            drops: DropReport::new(),
+            profile: FrameProfile::new(),
+            retained: RetainedLabels::new(config::current().retained_label_capacity),
+            draw_order: Vec::new(),
+            loading: false,
+            unfocused: false,
+            enabled: true,
+            paused: false,
+            last_shapes: Vec::new(),
+            last_labels: Vec::new(),
+            frozen_camera: None,
+            text_height_scale: 1.0,
+            settings: SettingsMenu::new(),
+            heatmap: Heatmap::new(),
+            calibration: CalibrationGrid::new(),
+            inspector: TextInspector::new(),
+            dpi: DpiScale::new(),
+            font: config::current().font.clone(),
+            configured_font: config::current().font.clone(),
+            atlas_size: None,
+            pending_font_rebuild: None,
+            pending_screenshot: None,
+        }
     }
 
-    fn window_size() -> (f32, f32) {
+    fn is_minimized() -> bool {
         unsafe { CSWindowImp::instance() }
-            .map(|w| (w.screen_width as f32, w.screen_height as f32))
-            .unwrap_or((1920.0, 1080.0))
+            .is_ok_and(|window| unsafe { IsIconic(window.window_handle) }.as_bool())
+    }
+
+    fn window_size() -> (f32, f32) {
+        static FALLING_BACK: AtomicBool = AtomicBool::new(false);
+
+        let size = unsafe { CSWindowImp::instance() }
+            .ok()
+            .map(|w| (w.screen_width as f32, w.screen_height as f32));
+        validated_window_dimensions(size, "window size", &FALLING_BACK)
     }
 
     fn window_resolution() -> (f32, f32) {
-        if let Ok(window) = unsafe { CSWindowImp::instance() } {
+        static FALLING_BACK: AtomicBool = AtomicBool::new(false);
+
+        let resolution = unsafe { CSWindowImp::instance() }.ok().map(|window| {
             match window.persistent_window_config.window_type {
                 CSWindowType::Windowed => (
                     window.persistent_window_config.windowed_screen_width as f32,
@@ -88,41 +337,384 @@ impl DebugTextRender {
                     window.persistent_window_config.borderless_screen_height as f32,
                 ),
             }
+        });
+        validated_window_dimensions(resolution, "window resolution", &FALLING_BACK)
+    }
+
+    /// Maps a label's font size to the window font scale it is drawn at.
+    fn font_scale(&mut self, config: &Config) -> impl Fn(&ResolvedText) -> f32 + use<> {
+        // Positions already arrive in physical pixels, so DPI only affects the text size
+        let dpi_scale =
+            self.dpi.update() * config.dpi_scale_multiplier * config.text_scale_factor();
+
+        // label.font_size is the pixel size the game wants (e.g., 18.0)
+        // self.font.size_px is the size the font atlas was built at (24.0 by default)
+        let font_size_px = self.font.size_px;
+        move |label: &ResolvedText| label.font_size / font_size_px * dpi_scale
+    }
+
+    /// What the overlay should do in the game's current state, given how `config` treats
+    /// loading screens and the game window being in the background. Hiding wins over freezing.
+    ///
+    /// There is no paused state to check as well: the game keeps running with its menus open,
+    /// even offline, so the pause key is the only way to stop the overlay.
+    fn inactive_behavior(&mut self, config: &Config) -> InactiveBehavior {
+        let loading = config.while_loading != InactiveBehavior::Show && game_state::is_loading();
+        if loading != self.loading {
+            tracing::info!(
+                "Loading screen {}",
+                if loading { "entered" } else { "left" }
+            );
+            self.loading = loading;
+        }
+        let unfocused =
+            config.while_unfocused != InactiveBehavior::Show && !game_state::is_focused();
+        if unfocused != self.unfocused {
+            tracing::info!(
+                "Game window {}",
+                if unfocused {
+                    "lost focus"
+                } else {
+                    "regained focus"
+                }
+            );
+            self.unfocused = unfocused;
+        }
+
+        [
+            (loading, config.while_loading),
+            (unfocused, config.while_unfocused),
+        ]
+        .into_iter()
+        .filter_map(|(active, behavior)| active.then_some(behavior))
+        .max()
+        .unwrap_or(InactiveBehavior::Show)
+    }
+
+    /// Validates the game's `text_pos_height_scale`, logging whenever it changes so the value
+    /// text sizes are based on shows up in the log.
+    fn update_text_height_scale(&mut self, scale: f32) -> f32 {
+        let scale = if scale.is_finite() && scale > 0.0 {
+            scale
+        } else {
+            1.0
+        };
+        if scale != self.text_height_scale {
+            tracing::info!(
+                "Text height scale changed from {} to {}",
+                self.text_height_scale,
+                scale
+            );
+            self.text_height_scale = scale;
+        }
+        scale
+    }
+
+    /// Frame time and queue readouts enabled in `config`, drawn like any other screen-space
+    /// text.
+    fn diagnostics_label(
+        ui: &Ui,
+        config: &Config,
+        frame: &FrameContext,
+        stats: &OverlayStats,
+        text_commands: usize,
+    ) -> ResolvedText {
+        let mut text = pool::take();
+        if config.show_frame_time {
+            let delta = ui.io().delta_time;
+            let fps = if delta > 0.0 { 1.0 / delta } else { 0.0 };
+            let _ = writeln!(text, "{:.2} ms ({:.0} FPS)", delta * 1000.0, fps);
+        }
+        if config.show_queue_stats {
+            let _ = writeln!(
+                text,
+                "Text: {}  Queue: {}/{}  Dropped: {}",
+                text_commands, stats.queue_len, stats.queue_capacity, stats.dropped_commands
+            );
+        }
+        text.pop();
+
+        ResolvedText {
+            text,
+            mode: None,
+            raw_pos: (DIAGNOSTICS_POS.0, DIAGNOSTICS_POS.1, 0.0),
+            screen_pos: DIAGNOSTICS_POS,
+            depth: None,
+            color: config.text_color(None, [1.0, 1.0, 1.0, 1.0]),
+            font_size: frame.font_size,
+        }
+    }
+
+    /// Draws `shapes`, then `labels` in `order` on top of them, into the draw list of a single
+    /// full-screen, input-less window.
+    ///
+    /// `font_scale` maps a label's font size to the window font scale it is drawn at.
+    fn draw_labels(
+        ui: &Ui,
+        config: &Config,
+        shapes: &[ResolvedShape],
+        labels: &[ResolvedText],
+        order: &[usize],
+        font_scale: impl Fn(&ResolvedText) -> f32,
+        window_size: (f32, f32),
+    ) {
+        let _padding = ui.push_style_var(imgui::StyleVar::WindowPadding([0.0, 0.0]));
+        ui.window("text_overlay")
+            .size([window_size.0, window_size.1], imgui::Condition::Always)
+            .position([0.0, 0.0], imgui::Condition::Always)
+            .no_decoration()
+            .focus_on_appearing(false)
+            .bring_to_front_on_focus(false)
+            .no_nav()
+            .draw_background(false)
+            .no_inputs()
+            .resizable(false)
+            .movable(false)
+            .collapsible(false)
+            .title_bar(false)
+            .build(|| {
+                for shape in shapes {
+                    style::draw_shape(ui, shape);
+                }
+
+                for &index in order {
+                    let label = &labels[index];
+                    let text = label.text.as_str();
+                    let (x, y) = label.screen_pos;
+
+                    debug_throttled!(
+                        RENDER_LOG,
+                        "Rendering text '{}' at screen position ({}, {})",
+                        text,
+                        x,
+                        y
+                    );
+
+                    // The draw list sizes text by the window font scale
+                    let font_scale = font_scale(label);
+                    ui.set_window_font_scale(font_scale);
+
+                    let snap = config.pixel_snap.for_mode(label.mode);
+                    let emphasis = config.emphasis(label.mode);
+                    if label
+                        .mode
+                        .is_some_and(|mode| config.vertical_modes.contains(&mode))
+                    {
+                        Self::draw_vertical_text(ui, config, label, font_scale, snap, emphasis);
+                        continue;
+                    }
+
+                    let align = config.text_align(label.mode, text);
+                    if config.background.enabled {
+                        let bounds = layout::text_bounds(
+                            ui,
+                            text,
+                            font_scale,
+                            align,
+                            config.tabular_digits,
+                            [x, y],
+                        );
+                        style::draw_background(
+                            ui,
+                            bounds,
+                            &config.background,
+                            config.text_scale_factor(),
+                            label.color[3],
+                        );
+                    }
+
+                    if align == TextAlign::Left {
+                        let pos = layout::snap_to_pixels([x, y], snap);
+                        Self::draw_text(ui, config, pos, text, label.color, font_scale, emphasis);
+                        continue;
+                    }
+
+                    // Align every line on its own rather than the block as a whole
+                    let line_height = layout::measure_text(ui, "", font_scale)[1];
+                    for (line_index, line) in text.split('\n').enumerate() {
+                        let width =
+                            layout::measure_line(ui, line, font_scale, config.tabular_digits)[0];
+                        let pos = [x + align.offset(width), y + line_index as f32 * line_height];
+                        let pos = layout::snap_to_pixels(pos, snap);
+                        Self::draw_text(ui, config, pos, line, label.color, font_scale, emphasis);
+                    }
+                }
+            });
+    }
+
+    /// Draws `label` top to bottom in columns running right to left, starting at its position.
+    fn draw_vertical_text(
+        ui: &Ui,
+        config: &Config,
+        label: &ResolvedText,
+        font_scale: f32,
+        snap: bool,
+        emphasis: Emphasis,
+    ) {
+        let (x, y) = label.screen_pos;
+        // CJK glyphs are square, so one line height fits a character either way
+        let cell_size = layout::measure_text(ui, "", font_scale)[1];
+        let cells = layout::vertical_cells(&label.text);
+
+        if config.background.enabled {
+            let bounds = layout::vertical_bounds(&cells, cell_size, [x, y]);
+            style::draw_background(
+                ui,
+                bounds,
+                &config.background,
+                config.text_scale_factor(),
+                label.color[3],
+            );
+        }
+
+        for cell in &cells {
+            // Centre each cell in its column, Latin runs included
+            let width = layout::measure_text(ui, cell.text, font_scale)[0];
+            let pos = [
+                x - cell.column as f32 * cell_size + (cell_size - width) * 0.5,
+                y + cell.row as f32 * cell_size,
+            ];
+            let pos = layout::snap_to_pixels(pos, snap);
+            Self::draw_text(
+                ui,
+                config,
+                pos,
+                cell.text,
+                label.color,
+                font_scale,
+                emphasis,
+            );
+        }
+    }
+
+    /// Draws `text` with its shadow and outline into the current window's draw list.
+    ///
+    /// With [`Config::tabular_digits`] set, numeric text is drawn one character at a time, each
+    /// centred in a cell as wide as the widest digit.
+    fn draw_text(
+        ui: &Ui,
+        config: &Config,
+        pos: [f32; 2],
+        text: &str,
+        color: [f32; 4],
+        font_scale: f32,
+        emphasis: Emphasis,
+    ) {
+        if !config.tabular_digits || !layout::is_numeric(text) {
+            Self::draw_run(ui, config, pos, text, color, font_scale, emphasis);
+            return;
+        }
+
+        let cell_width = layout::digit_width(ui, font_scale);
+        for (index, cluster) in text::clusters(text).enumerate() {
+            let width = layout::measure_text(ui, cluster, font_scale)[0];
+            let x = pos[0] + index as f32 * cell_width + (cell_width - width) * 0.5;
+            Self::draw_run(
+                ui,
+                config,
+                [x, pos[1]],
+                cluster,
+                color,
+                font_scale,
+                emphasis,
+            );
+        }
+    }
+
+    /// Draws `text` as is, with its shadow and outline, into the current window's draw list.
+    fn draw_run(
+        ui: &Ui,
+        config: &Config,
+        pos: [f32; 2],
+        text: &str,
+        color: [f32; 4],
+        font_scale: f32,
+        emphasis: Emphasis,
+    ) {
+        let draw = || {
+            style::draw_shadow(ui, pos, text, &config.shadow, font_scale, color[3]);
+            style::draw_outline(
+                ui,
+                pos,
+                text,
+                &config.outline,
+                config.text_scale_factor(),
+                color[3],
+            );
+            let draw_list = ui.get_window_draw_list();
+            draw_list.add_text(pos, color, text);
+            if emphasis.bold {
+                draw_list.add_text([pos[0] + config.text_scale_factor(), pos[1]], color, text);
+            }
+        };
+
+        if emphasis.italic {
+            let height = layout::measure_text(ui, text, font_scale)[1];
+            style::draw_slanted(pos[1] + height, draw);
         } else {
-            (1920.0, 1080.0)
+            draw();
         }
     }
 }
 
 impl ImguiRenderLoop for DebugTextRender {
     fn initialize(&mut self, ctx: &mut Context, _render_context: &mut dyn RenderContext) {
-        let font_data = std::fs::read("C:\\Windows\\Fonts\\msgothic.ttc")
-            .expect("Failed to read font file (msgothic.ttc)");
-        let glyph_ranges = imgui::FontGlyphRanges::from_slice(&[
-            0x0020, 0x00FF, // Basic Latin + Latin Supplement
-            0x3000, 0x30FF, // Japanese punctuation, Hiragana, Katakana
-            0x31F0, 0x31FF, // Katakana Phonetic Extensions
-            0x3400, 0x4DBF, // CJK Unified Ideographs Extension A
-            0x4E00, 0x9FFF, // CJK Unified Ideographs
-            0xF900, 0xFAFF, // CJK Compatibility Ideographs
-            0xFF00, 0xFFEF, // Halfwidth and Fullwidth Forms
-            0x2500, 0x257F, // Box Drawing
-            0x2580, 0x259F, // Block Elements (includes ■)
-            0x25A0, 0x25FF, // Geometric Shapes (includes ■ specifically)
-            0,
-        ]);
-        ctx.fonts().add_font(&[imgui::FontSource::TtfData {
-            data: &font_data,
-            size_pixels: BASE_IMGUI_FONT_SIZE_PX,
-            config: Some(imgui::FontConfig {
-                oversample_h: 3,
-                oversample_v: 1,
-                pixel_snap_h: true,
-                glyph_ranges,
-                ..Default::default()
-            }),
-        }]);
-        ctx.fonts().build_alpha8_texture();
+        let fonts = ctx.fonts();
+        fonts::add_fonts(fonts, &self.font);
+        let texture = fonts.build_alpha8_texture();
+        self.atlas_size = Some((texture.width, texture.height));
+    }
+
+    fn before_render(&mut self, ctx: &mut Context, render_context: &mut dyn RenderContext) {
+        // ImGui only allows the atlas to change between frames, which is why `render` defers
+        // rebuilds to here rather than doing them itself.
+        let Some(font) = self.pending_font_rebuild.take() else {
+            return;
+        };
+
+        // A missing primary font would silently fall back to ImGui's default font,
+        // which is a worse outcome for a reload than keeping what we have
+        if let Err(e) = std::fs::metadata(&font.path) {
+            tracing::error!(
+                "Not rebuilding font atlas, can't read {}: {}",
+                font.path.display(),
+                e
+            );
+            return;
+        }
+
+        let fonts = ctx.fonts();
+        let previous_texture = fonts.tex_id;
+        fonts.clear();
+        fonts::add_fonts(fonts, &font);
+
+        let uploaded = {
+            let texture = fonts.build_rgba32_texture();
+            let size = (texture.width, texture.height);
+            render_context
+                .load_texture(texture.data, texture.width, texture.height)
+                .map(|texture_id| (texture_id, size))
+        };
+        match uploaded {
+            Ok((texture_id, size)) => {
+                fonts.tex_id = texture_id;
+                self.atlas_size = Some(size);
+                tracing::info!("Rebuilt font atlas ({}x{}) with {:?}", size.0, size.1, font);
+                self.font = font;
+            }
+            Err(e) => {
+                tracing::error!(
+                    "Failed to upload rebuilt font atlas, keeping the previous one: {:?}",
+                    e
+                );
+                // The previous texture is still alive on the GPU; rasterize the glyph data
+                // that matches it again so UVs line up
+                fonts.clear();
+                fonts::add_fonts(fonts, &self.font);
+                let _ = fonts.build_rgba32_texture();
+                fonts.tex_id = previous_texture;
+            }
+        }
     }
 
     fn render(&mut self, ui: &mut Ui) {
@@ -138,235 +730,592 @@ impl ImguiRenderLoop for DebugTextRender {
             .collapsible(false)
             .title_bar(false)
             .build(|| ui.text("."));
+
+        if let Some(dir) = self.pending_screenshot.take()
+            && let Ok(window) = unsafe { CSWindowImp::instance() }
+            && let Some(screenshot) = Screenshot::capture(window.window_handle)
+        {
+            screenshot.save_in_background(dir);
+        }
+
+        let stats = OverlayStats {
+            atlas_size: self.atlas_size,
+            retained_labels: self.retained.len(),
+            retained_label_capacity: self.retained.capacity(),
+            queue_len: TEXT_RENDER_QUEUE.len(),
+            queue_capacity: TEXT_RENDER_QUEUE.capacity(),
+            dropped_commands: DROPPED_COMMANDS.load(Ordering::Relaxed),
+            phase_ms: self.profile.averages_ms(),
+        };
+        if let Some(font) = self.settings.draw(ui, &stats) {
+            self.pending_font_rebuild = Some(font);
+        }
+        if ui.is_key_pressed(FONT_RELOAD_KEY) {
+            tracing::info!("Reloading fonts");
+            self.pending_font_rebuild = Some(config::current().font.clone());
+        }
+        if ui.is_key_pressed(QUEUE_STATS_KEY) {
+            config::update(|config| config.show_queue_stats = !config.show_queue_stats);
+        }
+        if let Some(dir) = &config::current().screenshot_dir
+            && ui.is_key_pressed(config::current().screenshot_key.0)
+        {
+            self.pending_screenshot = Some(dir.clone());
+        }
+        self.heatmap.handle_hotkey(ui);
+        self.calibration.handle_hotkey(ui);
+        self.inspector.handle_hotkey(ui);
+        if ui.is_key_pressed(config::current().toggle_key.0) {
+            self.enabled = !self.enabled;
+            tracing::info!(
+                "Overlay {}",
+                if self.enabled { "enabled" } else { "disabled" }
+            );
+        }
+        if ui.is_key_pressed(PAUSE_KEY) {
+            self.paused = !self.paused;
+            tracing::info!("Overlay {}", if self.paused { "paused" } else { "resumed" });
+        }
+        if ui.is_key_pressed(FREEZE_CAMERA_KEY) {
+            self.frozen_camera = match self.frozen_camera {
+                Some(_) => {
+                    tracing::info!("Camera projection live");
+                    None
+                }
+                None => {
+                    let camera = CameraBasis::current(config::current().camera_index);
+                    match &camera {
+                        Some(camera) => tracing::info!("Camera projection frozen at {:?}", camera),
+                        None => tracing::warn!("No camera to freeze the projection at"),
+                    }
+                    camera
+                }
+            };
+        }
+        CAPTURE_PAUSED.store(
+            self.paused && !config::current().capture_while_paused,
+            Ordering::Relaxed,
+        );
+        // Nothing is visible while minimized, and the window reports no usable size anyway
+        if !self.enabled || Self::is_minimized() {
+            clear_queue();
+            return;
+        }
+
         let Ok(buffer) =
             (unsafe { RendMan::instance().map(|rm| rm.debug_ez_draw.current_buffer()) })
         else {
             return;
         };
         let state = &buffer.ez_draw_state.base;
-        while let Some(event) = TEXT_RENDER_QUEUE.pop() {
-            match event {
-                DrawCommand::SetOffset(x, y) => {
-                    self.offset = (x, y);
-                }
-                DrawCommand::Text(text, x, y, z, render_mode) => {
-                    let (new_x, new_y) = match render_mode {
-                        EzDrawTextCoordMode::HavokPosition2
-                        | EzDrawTextCoordMode::HavokPosition3 => {
-                            let camera = unsafe { CSCamera::instance() }.unwrap();
-                            let cam = &camera.pers_cam_1;
-
-                            let cam_right = cam.right();
-                            let cam_up = cam.up();
-                            let cam_forward = cam.forward();
-                            let cam_pos_h = cam.position();
-
-                            let cam_right_v = Vector3::new(cam_right.0, cam_right.1, cam_right.2);
-                            let cam_up_v = Vector3::new(cam_up.0, cam_up.1, cam_up.2);
-                            let cam_forward_v =
-                                Vector3::new(cam_forward.0, cam_forward.1, cam_forward.2);
-                            let cam_pos = Vector3::new(cam_pos_h.0, cam_pos_h.1, cam_pos_h.2);
-
-                            let world_pos = Vector3::new(x, y, z);
-                            let rel = world_pos - cam_pos;
-
-                            let z_cam = cam_forward_v.dot(&rel);
-                            if z_cam <= 0.0 {
-                                (f32::NAN, f32::NAN)
-                            } else {
-                                let x_cam = cam_right_v.dot(&rel);
-                                let y_cam = cam_up_v.dot(&rel);
-
-                                let fov_rad = cam.fov;
-                                let m11 = 1.0 / (0.5 * fov_rad).tan();
-                                let m00 = m11 / cam.aspect_ratio;
-
-                                let ndc_x = x_cam * m00 / z_cam;
-                                let ndc_y = y_cam * m11 / z_cam;
-
-                                let screen_size = Self::window_size();
-
-                                let screen_x = (ndc_x * 0.5 + 0.5) * screen_size.0;
-                                let screen_y = (ndc_y * -0.5 + 0.5) * screen_size.1;
-
-                                (screen_x, screen_y)
-                            }
-                        }
-                        EzDrawTextCoordMode::ScreenSpace0 | EzDrawTextCoordMode::ScreenSpace1 => {
-                            let resolution = Self::window_resolution();
-                            let size = Self::window_size();
-                            let scale_x = size.0 / resolution.0;
-                            let scale_y = size.1 / resolution.1;
-                            (x * scale_x, y * scale_y)
-                        }
-                        EzDrawTextCoordMode::Normalized4k => {
-                            let screen_size = Self::window_resolution();
-                            let diff_x: f32 = screen_size.0 / 3840.0;
-                            let diff_y: f32 = screen_size.1 / 2160.0;
-                            (x * diff_x, y * diff_y)
-                        }
-                        EzDrawTextCoordMode::Normalized1080p => {
-                            let screen_size = Self::window_resolution();
-                            let diff_x: f32 = screen_size.0 / 1920.0;
-                            let diff_y: f32 = screen_size.1 / 1080.0;
-                            (x * diff_x, y * diff_y)
-                        }
-                    };
-                    if !new_x.is_finite() || !new_y.is_finite() {
-                        continue;
-                    }
 
-                    let offset_x = new_x + self.offset.0;
-                    let offset_y = new_y + self.offset.1;
-                    self.offset = (0.0, 0.0);
+        let config = config::current();
+        if config.font != self.configured_font {
+            self.configured_font = config.font.clone();
+            self.pending_font_rebuild = Some(config.font.clone());
+        }
 
-                    tracing::debug!(
-                        "Rendering text '{}' at screen position ({}, {})",
-                        text,
-                        offset_x,
-                        offset_y
-                    );
+        let behavior = self.inactive_behavior(&config);
+        if behavior == InactiveBehavior::Hide {
+            // Drop whatever was captured meanwhile so none of it flashes up once we resume,
+            // including labels that would otherwise linger from before
+            clear_queue();
+            self.retained.clear();
+            return;
+        }
+        if behavior == InactiveBehavior::Freeze {
+            clear_queue();
+        }
+
+        if self.paused || behavior == InactiveBehavior::Freeze {
+            let font_scale = self.font_scale(&config);
+            Self::draw_labels(
+                ui,
+                &config,
+                &self.last_shapes,
+                &self.last_labels,
+                &self.draw_order,
+                font_scale,
+                Self::window_size(),
+            );
+            self.inspector.draw(ui);
+            return;
+        }
 
-                    // Hash the coordinates and text to create a unique window name
-                    let mut hasher = std::collections::hash_map::DefaultHasher::new();
-                    (x as u32).hash(&mut hasher);
-                    (y as u32).hash(&mut hasher);
-                    (offset_x as u32).hash(&mut hasher);
-                    (offset_y as u32).hash(&mut hasher);
-                    text.hash(&mut hasher);
-                    let _guard = ui.push_id(hasher.finish().to_string());
-                    let window_size = Self::window_size();
-                    ui.window(format!("text_window_{x}_{y}"))
-                        .size([window_size.0, window_size.1], imgui::Condition::Always)
-                        .position([offset_x, offset_y], imgui::Condition::Always)
-                        .no_decoration()
-                        .focus_on_appearing(false)
-                        .focused(false)
-                        .draw_background(false)
-                        .no_inputs()
-                        .resizable(false)
-                        .movable(false)
-                        .collapsible(false)
-                        .title_bar(false)
-                        .build(|| {
-                            // Normalize color from [0-255] to [0.0-1.0]
-                            let text_color = state.text_color;
-                            let _ = ui.push_style_color(
-                                imgui::StyleColor::Text,
-                                [
-                                    text_color.r() as f32 / 255.0,
-                                    text_color.g() as f32 / 255.0,
-                                    text_color.b() as f32 / 255.0,
-                                    text_color.a() as f32 / 255.0,
-                                ],
-                            );
-
-                            // state.font_size is the pixel size the game wants (e.g., 18.0)
-                            // BASE_IMGUI_FONT_SIZE_PX is the size the font was loaded at (24.0)
-                            // Multiply by text_pos_height_scale to match game's resolution scaling
-                            let font_scale = state.font_size / BASE_IMGUI_FONT_SIZE_PX;
-
-                            ui.set_window_font_scale(font_scale);
-                            ui.text(text);
-                        });
+        let frame = FrameContext {
+            window_size: Self::window_size(),
+            window_resolution: Self::window_resolution(),
+            camera: self
+                .frozen_camera
+                .or_else(|| CameraBasis::current(config.camera_index)),
+            // Normalize color from [0-255] to [0.0-1.0]
+            text_color: [
+                state.text_color.r() as f32 / 255.0,
+                state.text_color.g() as f32 / 255.0,
+                state.text_color.b() as f32 / 255.0,
+                state.text_color.a() as f32 / 255.0,
+            ],
+            // The game scales its own text with the resolution through text_pos_height_scale
+            font_size: state.font_size * self.update_text_height_scale(state.text_pos_height_scale),
+        };
+
+        self.profile.begin_frame(config.profile_render);
+        let commands: Vec<DrawCommand> = std::iter::from_fn(|| TEXT_RENDER_QUEUE.pop())
+            .map(|queued| {
+                self.latency.record(queued.queued_at.elapsed());
+                queued.command
+            })
+            .collect();
+        self.profile.lap(Phase::Drain);
+        self.latency.report_if_due();
+        self.drops
+            .report_if_due(DROPPED_COMMANDS.load(Ordering::Relaxed));
+        self.recorder
+            .record(config.record_path.as_deref(), &frame, &commands);
+        let text_commands = commands
+            .iter()
+            .filter(|command| matches!(command, DrawCommand::Text(..)))
+            .count();
+        let mut shapes = self.pipeline.process_shapes(&commands, &frame, &config);
+        let mut labels = self.pipeline.process_commands(commands, &frame, &config);
+        self.profile.lap(Phase::Resolve);
+        self.capture.record(
+            config.capture_path.as_deref(),
+            config.capture_max_bytes,
+            &labels,
+        );
+        self.csv.record(config.csv_path.as_deref(), &labels);
+        inspector::record(&labels);
+        self.calibration.resolve(
+            &mut self.pipeline,
+            &frame,
+            &config,
+            &mut labels,
+            &mut shapes,
+        );
+        if config.merge_fragments {
+            let font_size_px = self.font.size_px;
+            labels = pipeline::merge_fragments(labels, config.merge_tolerance_px, |label| {
+                layout::measure_text(ui, &label.text, label.font_size / font_size_px)[0]
+            });
+        }
+
+        let now = Instant::now();
+        self.retained.set_capacity(config.retained_label_capacity);
+        let max_display = config.max_display_duration();
+        let fade_in = config.fade_in_duration();
+        for label in &mut labels {
+            let key = retained::label_key(label);
+            self.retained.observe(key, label, now);
+
+            let Some(entry) = self.retained.get(key) else {
+                continue;
+            };
+            let age = now.duration_since(entry.first_seen);
+            if let Some(max_display) = max_display {
+                label.color[3] *= retained::duration_cap_alpha(age, max_display);
+            }
+            if let Some(fade_in) = fade_in {
+                label.color[3] *= retained::fade_in_alpha(age, fade_in);
+            }
+        }
+
+        let hold = config.hold_duration();
+        let fade_out = config.fade_out_duration();
+        if hold.is_some() || fade_out.is_some() {
+            let hold = hold.unwrap_or_default();
+            // Everything observed above has `last_seen == now`, so this only picks up labels
+            // the game has stopped drawing
+            labels.extend(self.retained.iter().filter_map(|(_, entry)| {
+                let since = now.duration_since(entry.last_seen);
+                let mut alpha = retained::linger_alpha(since, hold, fade_out);
+                if since.is_zero() || alpha <= 0.0 {
+                    return None;
+                }
+                if let Some(fade_in) = fade_in {
+                    let age = entry.last_seen.duration_since(entry.first_seen);
+                    alpha *= retained::fade_in_alpha(age, fade_in);
                 }
+
+                let mut label = entry.label.clone();
+                label.color[3] *= alpha;
+                Some(label)
+            }));
+        }
+        labels.retain(|label| label.color[3] > 0.0);
+        // Only now that lingering and calibration labels are in, so the cap bounds everything
+        // laid out and drawn below
+        let over_budget = pipeline::apply_label_budget(&mut labels, config.max_labels_per_frame);
+        DROPPED_COMMANDS.fetch_add(over_budget as u64, Ordering::Relaxed);
+
+        let font_scale = self.font_scale(&config);
+
+        if config.wrap_width_px > 0.0 {
+            for label in &mut labels {
+                let scale = font_scale(label);
+                label.text = layout::wrap_text(
+                    std::mem::take(&mut label.text),
+                    config.wrap_width_px,
+                    |line| layout::measure_text(ui, line, scale)[0],
+                );
             }
         }
+
+        if config.font.rtl {
+            // After wrapping, so the lines still follow in reading order and only the
+            // characters within each one are mirrored
+            for label in labels.iter_mut().filter(|label| text::is_rtl(&label.text)) {
+                let visual = text::rtl_visual_order(&label.text);
+                pool::recycle(std::mem::replace(&mut label.text, visual));
+            }
+        }
+
+        if config.keep_on_screen {
+            // Only the game's own screen-space text; API text is placed exactly where asked
+            for label in labels
+                .iter_mut()
+                .filter(|label| label.mode.is_some_and(|mode| !mode.is_world_space()))
+            {
+                let (x, y) = label.screen_pos;
+                let align = config.text_align(label.mode, &label.text);
+                let bounds = layout::text_bounds(
+                    ui,
+                    &label.text,
+                    font_scale(label),
+                    align,
+                    config.tabular_digits,
+                    [x, y],
+                );
+                let (shift_x, shift_y) = layout::shift_onto_screen(bounds, frame.window_size);
+                label.screen_pos = (x + shift_x, y + shift_y);
+            }
+        }
+
+        if config.show_frame_time || config.show_queue_stats {
+            labels.push(Self::diagnostics_label(
+                ui,
+                &config,
+                &frame,
+                &stats,
+                text_commands,
+            ));
+        }
+
+        pipeline::draw_order(&labels, &mut self.draw_order);
+        for &index in &self.draw_order {
+            self.heatmap
+                .record(labels[index].screen_pos, frame.window_size);
+        }
+
+        self.profile.lap(Phase::Layout);
+
+        Self::draw_labels(
+            ui,
+            &config,
+            &shapes,
+            &labels,
+            &self.draw_order,
+            font_scale,
+            frame.window_size,
+        );
+        self.profile.lap(Phase::Submit);
+
+        self.heatmap.draw(ui, frame.window_size);
+        self.inspector.draw(ui);
+
+        for label in std::mem::replace(&mut self.last_labels, labels) {
+            pool::recycle(label.text);
+        }
+        self.last_shapes = shapes;
     }
 }
 
-fn init() {
-    setup_logging();
+fn init(config_path: &Path) {
+    // Logging is set up from the config, so anything loading it reports goes straight to the
+    // console
+    tracing::subscriber::with_default(tracing_subscriber::fmt().finish(), || {
+        config::load(config_path)
+    });
+    setup_logging(&config::current().log);
+
+    let dump_dir = config_path.parent().map(Path::to_owned).unwrap_or_default();
+    std::panic::set_hook(Box::new(move |info| custom_panic_hook(info, &dump_dir)));
+
+    // The queue is sized from the config, so it has to be loaded before the hooks go in
+    LazyLock::force(&TEXT_RENDER_QUEUE);
+    if let Some(port) = config::current().tcp_port {
+        events::start_tcp(port);
+    }
 
-    std::panic::set_hook(Box::new(custom_panic_hook));
     let program = Program::current();
-    let text_request_va = program.rva_to_va(TEXT_RENDER_REQUEST_RVA).unwrap();
-    unsafe {
-        DrawTextRenderRequest
-            .initialize(
-                transmute::<u64, unsafe extern "C" fn(*mut CSEzDraw, *mut F32Vector4, *const u16)>(
-                    text_request_va,
-                ),
-                |ez_draw: *mut CSEzDraw, pos: *mut F32Vector4, text: *const u16| {
-                    let text_str = u16_ptr_to_string(text);
-                    let x = (*pos).0;
-                    let y = (*pos).1;
-                    let z = (*pos).2;
-                    let render_mode = (*ez_draw)
-                        .current_buffer()
-                        .ez_draw_state
-                        .base
-                        .text_coord_mode;
-                    tracing::debug!(
-                        "DrawTextRenderRequest: {:?},  {}, {:?}",
-                        render_mode,
-                        text_str,
-                        *pos
-                    );
+    let version = signatures::detect_version(program);
+    let (rvas, exact) = signatures::rvas_for(version);
+    let text_request_va =
+        signatures::resolve(program, &signatures::DRAW_TEXT, rvas.draw_text, exact);
+    let draw_text_with_offset_va = signatures::resolve(
+        program,
+        &signatures::DRAW_TEXT_WITH_OFFSET,
+        rvas.draw_text_with_offset,
+        exact,
+    );
+    // A hook whose address doesn't check out is skipped, the overlay still shows the other one
+    if let Some(text_request_va) = text_request_va {
+        let hooked = unsafe {
+            DrawTextRenderRequest
+                .initialize(
+                    transmute::<
+                        u64,
+                        unsafe extern "C" fn(*mut CSEzDraw, *mut F32Vector4, *const u16),
+                    >(text_request_va),
+                    |ez_draw: *mut CSEzDraw, pos: *mut F32Vector4, text: *const u16| {
+                        let config = config::current();
+                        if config.mirror_native_text {
+                            DrawTextRenderRequest.call(ez_draw, pos, text);
+                        }
+                        let Some(text_str) = filter_captured(u16_ptr_to_string(text), &config)
+                        else {
+                            return;
+                        };
+                        let x = (*pos).0;
+                        let y = (*pos).1;
+                        let z = (*pos).2;
+                        let render_mode = (*ez_draw)
+                            .current_buffer()
+                            .ez_draw_state
+                            .base
+                            .text_coord_mode;
+                        // Lets RUST_LOG select logging by coordinate mode
+                        let _span = hot_span!("draw_text", render_mode);
+                        debug_throttled!(
+                            DRAW_TEXT_LOG,
+                            "DrawTextRenderRequest: {:?},  {}, {:?}",
+                            render_mode,
+                            text_str,
+                            *pos
+                        );
 
-                    TEXT_RENDER_QUEUE.force_push(DrawCommand::Text(text_str, x, y, z, render_mode));
-                },
-            )
-            .unwrap()
-            .enable()
-            .unwrap();
-    }
-    let draw_text_with_offset_va = program.rva_to_va(DRAW_TEXT_WITH_OFFSET_RVA).unwrap();
-    unsafe {
-        DrawTextWithOffset
-            .initialize(
-                transmute::<
-                    u64,
-                    unsafe extern "C" fn(
-                        *mut CSEzDraw,
-                        *mut F32Vector4,
-                        *mut F32Vector2,
-                        *const u16,
-                    ),
-                >(draw_text_with_offset_va),
-                |ez_draw: *mut CSEzDraw,
-                 pos: *mut F32Vector4,
-                 offset: *mut F32Vector2,
-                 text: *const u16| {
-                    TEXT_RENDER_QUEUE.force_push(DrawCommand::SetOffset((*offset).0, (*offset).1));
-                    let text_str = u16_ptr_to_string(text);
-                    let x = (*pos).0;
-                    let y = (*pos).1;
-                    let z = (*pos).2;
-
-                    let current_buffer = (*ez_draw).current_buffer();
-
-                    let render_mode = current_buffer.ez_draw_state.base.text_coord_mode;
-                    tracing::debug!(
-                        "DrawTextWithOffset: {:?},  {}, {:?}, {:?}",
-                        render_mode,
-                        text_str,
-                        *pos,
-                        *offset
-                    );
+                        push_command(DrawCommand::Text(
+                            text_str,
+                            x,
+                            y,
+                            z,
+                            render_mode.into(),
+                            None,
+                        ));
+                    },
+                )
+                .and_then(|detour| {
+                    if config::current().hook_draw_text {
+                        detour.enable()
+                    } else {
+                        Ok(())
+                    }
+                })
+        };
+        if let Err(e) = hooked {
+            tracing::error!("Failed to hook {}: {:?}", signatures::DRAW_TEXT.name, e);
+        }
+    }
+    if let Some(draw_text_with_offset_va) = draw_text_with_offset_va {
+        let hooked = unsafe {
+            DrawTextWithOffset
+                .initialize(
+                    transmute::<
+                        u64,
+                        unsafe extern "C" fn(
+                            *mut CSEzDraw,
+                            *mut F32Vector4,
+                            *mut F32Vector2,
+                            *const u16,
+                        ),
+                    >(draw_text_with_offset_va),
+                    |ez_draw: *mut CSEzDraw,
+                     pos: *mut F32Vector4,
+                     offset: *mut F32Vector2,
+                     text: *const u16| {
+                        let config = config::current();
+                        if config.mirror_native_text {
+                            DrawTextWithOffset.call(ez_draw, pos, offset, text);
+                        }
+                        let Some(text_str) = filter_captured(u16_ptr_to_string(text), &config)
+                        else {
+                            return;
+                        };
+                        let x = (*pos).0;
+                        let y = (*pos).1;
+                        let z = (*pos).2;
+
+                        let current_buffer = (*ez_draw).current_buffer();
 
-                    TEXT_RENDER_QUEUE.force_push(DrawCommand::Text(text_str, x, y, z, render_mode));
-                },
-            )
-            .unwrap()
-            .enable()
-            .unwrap();
+                        let render_mode = current_buffer.ez_draw_state.base.text_coord_mode;
+                        let _span = hot_span!("draw_text_with_offset", render_mode);
+                        debug_throttled!(
+                            DRAW_TEXT_WITH_OFFSET_LOG,
+                            "DrawTextWithOffset: {:?},  {}, {:?}, {:?}",
+                            render_mode,
+                            text_str,
+                            *pos,
+                            *offset
+                        );
+
+                        push_command(DrawCommand::Text(
+                            text_str,
+                            x,
+                            y,
+                            z,
+                            render_mode.into(),
+                            Some(((*offset).0, (*offset).1)),
+                        ));
+                    },
+                )
+                .and_then(|detour| {
+                    if config::current().hook_draw_text_with_offset {
+                        detour.enable()
+                    } else {
+                        Ok(())
+                    }
+                })
+        };
+        if let Err(e) = hooked {
+            tracing::error!(
+                "Failed to hook {}: {:?}",
+                signatures::DRAW_TEXT_WITH_OFFSET.name,
+                e
+            );
+        }
     }
 
-    std::thread::spawn(|| {
+    let config_path = config_path.to_owned();
+    std::thread::spawn(move || {
         let program = Program::current();
         wait_for_system_init(&program, Duration::MAX).expect("System initialization timed out");
 
-        if let Err(e) = Hudhook::builder()
-            .with::<ImguiDx12Hooks>(DebugTextRender::new())
-            .build()
-            .apply()
-        {
+        if let Some(name) = &config::current().pipe_name {
+            events::start_pipe(name);
+        }
+        if let Some(name) = &config::current().shared_memory_name {
+            shared_memory::start(name);
+        }
+
+        let backend = match config::current().backend {
+            RenderBackend::Auto => detect_backend(),
+            backend => backend,
+        };
+        tracing::info!("Attaching ImGui hooks to {:?}", backend);
+
+        // The DLL may have been unloaded while we waited for the game
+        if SHUTTING_DOWN.load(Ordering::Acquire) {
+            return;
+        }
+
+        let builder = Hudhook::builder();
+        let builder = match backend {
+            RenderBackend::Dx11 => builder.with::<ImguiDx11Hooks>(DebugTextRender::new()),
+            _ => builder.with::<ImguiDx12Hooks>(DebugTextRender::new()),
+        };
+        if let Err(e) = builder.build().apply() {
             tracing::error!("Failed to apply ImGui hooks: {:?}", e);
+        } else {
+            log_startup_banner(version, text_request_va, draw_text_with_offset_va, backend);
         }
+
+        config::watch(&config_path, &SHUTTING_DOWN);
     });
 }
 
+/// Logs what the overlay settled on at startup in one block, so a bug report's log shows it.
+fn log_startup_banner(
+    version: Option<GameVersion>,
+    draw_text_va: Option<u64>,
+    draw_text_with_offset_va: Option<u64>,
+    backend: RenderBackend,
+) {
+    let config = config::current();
+    let hook = |va: Option<u64>, configured: bool, enabled: bool| match va {
+        Some(va) if enabled => format!("{va:#x}"),
+        Some(va) if !configured => format!("{va:#x} (disabled in config)"),
+        Some(va) => format!("{va:#x} (not hooked)"),
+        None => "not found".to_owned(),
+    };
+    tracing::info!(
+        "textrender {} started\n  \
+         game version: {}\n  \
+         {}: {}\n  \
+         {}: {}\n  \
+         font: {}\n  \
+         queue capacity: {}\n  \
+         backend: {:?}",
+        env!("CARGO_PKG_VERSION"),
+        version.map_or_else(|| "unknown".to_owned(), |version| version.to_string()),
+        signatures::DRAW_TEXT.name,
+        hook(
+            draw_text_va,
+            config.hook_draw_text,
+            DrawTextRenderRequest.is_enabled()
+        ),
+        signatures::DRAW_TEXT_WITH_OFFSET.name,
+        hook(
+            draw_text_with_offset_va,
+            config.hook_draw_text_with_offset,
+            DrawTextWithOffset.is_enabled()
+        ),
+        config.font.path.display(),
+        TEXT_RENDER_QUEUE.capacity(),
+        backend,
+    );
+}
+
+/// Removes every hook when the DLL is unloaded while the game keeps running, so nothing jumps
+/// into unmapped code afterwards.
+///
+/// Runs under the loader lock, so it can't wait for the background threads. They check
+/// [`SHUTTING_DOWN`] instead and stop at their next wakeup.
+fn shutdown() {
+    SHUTTING_DOWN.store(true, Ordering::Release);
+
+    if DrawTextRenderRequest.is_enabled()
+        && let Err(e) = unsafe { DrawTextRenderRequest.disable() }
+    {
+        tracing::error!("Failed to disable DrawTextRenderRequest detour: {:?}", e);
+    }
+    if DrawTextWithOffset.is_enabled()
+        && let Err(e) = unsafe { DrawTextWithOffset.disable() }
+    {
+        tracing::error!("Failed to disable DrawTextWithOffset detour: {:?}", e);
+    }
+
+    // Removes the ImGui present/resize hooks hudhook installed through MinHook
+    if let Err(e) = unsafe { hudhook::mh::MH_Uninitialize() }.ok() {
+        tracing::error!("Failed to remove ImGui hooks: {:?}", e);
+    }
+    tracing::info!("Hooks removed, unloading");
+}
+
+/// Picks the backend from the graphics runtime the game has loaded by now.
+///
+/// The game ships DX12 only, so d3d11.dll without d3d12.dll means a mod or compatibility layer
+/// is running it on DX11.
+fn detect_backend() -> RenderBackend {
+    let loaded = |module: PCWSTR| unsafe { GetModuleHandleW(module) }.is_ok();
+    if !loaded(w!("d3d12.dll")) && loaded(w!("d3d11.dll")) {
+        RenderBackend::Dx11
+    } else {
+        RenderBackend::Dx12
+    }
+}
+
+/// Path of the config file next to this DLL, falling back to the working directory.
+fn config_path(hinst: HINSTANCE) -> PathBuf {
+    let mut buffer = vec![0u16; 32768];
+    let len = unsafe { GetModuleFileNameW(HMODULE(hinst.0), &mut buffer) } as usize;
+    if len == 0 || len == buffer.len() {
+        return PathBuf::from(config::FILE_NAME);
+    }
+
+    let dll_path = PathBuf::from(OsString::from_wide(&buffer[..len]));
+    dll_path.with_file_name(config::FILE_NAME)
+}
+
 /// DLL entry point function.
 ///
 /// # Safety
@@ -374,12 +1323,55 @@ fn init() {
 /// during DLL loading, unloading, and thread attach/detach events.
 #[unsafe(no_mangle)]
 #[allow(non_snake_case)]
-pub unsafe extern "C" fn DllMain(hinst: HINSTANCE, reason: u32, _reserved: usize) -> bool {
-    if reason == DLL_PROCESS_ATTACH {
-        unsafe { DisableThreadLibraryCalls(hinst).ok() };
+pub unsafe extern "C" fn DllMain(hinst: HINSTANCE, reason: u32, reserved: usize) -> bool {
+    match reason {
+        DLL_PROCESS_ATTACH => {
+            unsafe { DisableThreadLibraryCalls(hinst).ok() };
 
-        LazyLock::force(&TEXT_RENDER_QUEUE);
-        init();
-    };
+            init(&config_path(hinst));
+        }
+        // A non-zero `reserved` means the process is exiting and every other thread is already
+        // gone, so there is nothing left to call into the hooks
+        DLL_PROCESS_DETACH if reserved == 0 => shutdown(),
+        _ => {}
+    }
     true
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unpaired_surrogate_decodes_to_replacement_character() {
+        let units: [u16; 6] = [0x48, 0xD800, 0x69, 0xDC00, 0x21, 0];
+        assert_eq!(u16_ptr_to_string(units.as_ptr()), "H\u{FFFD}i\u{FFFD}!");
+    }
+
+    #[test]
+    fn cut_off_text_keeps_supplementary_plane_characters_whole() {
+        // U+20000, the first Supplementary Ideographic Plane character, straddling the cut
+        let mut units = vec![u16::from(b'a'); MAX_TEXT_UNITS - 1];
+        units.extend([0xD840, 0xDC00, 0]);
+
+        let text = u16_ptr_to_string(units.as_ptr());
+        assert_eq!(text.len(), MAX_TEXT_UNITS - 1);
+        assert!(text.bytes().all(|byte| byte == b'a'));
+
+        // Whole when it fits
+        let text = u16_ptr_to_string(units[MAX_TEXT_UNITS - 1..].as_ptr());
+        assert_eq!(text, "\u{20000}");
+    }
+
+    #[test]
+    fn full_queue_counts_dropped_commands() {
+        let before = DROPPED_COMMANDS.load(Ordering::Relaxed);
+        for _ in 0..TEXT_RENDER_QUEUE.capacity() + 5 {
+            push_command(DrawCommand::ScreenText("label".to_owned(), 0.0, 0.0));
+        }
+
+        assert!(DROPPED_COMMANDS.load(Ordering::Relaxed) - before >= 5);
+        assert_eq!(TEXT_RENDER_QUEUE.len(), TEXT_RENDER_QUEUE.capacity());
+        clear_queue();
+    }
+}
@@ -1,13 +1,145 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    ffi::c_void,
+    fs::File,
+    hash::{Hash, Hasher},
+    io::{self, Write},
+    os::windows::io::AsRawHandle,
+    path::{Path, PathBuf},
+    sync::{Mutex, OnceLock},
+    time::{Duration, Instant, SystemTime},
+};
+
 use hudhook::windows::Win32::System::Console::{ATTACH_PARENT_PROCESS, AttachConsole};
+use tracing_appender::non_blocking::{NonBlocking, WorkerGuard};
 use tracing_panic::panic_hook;
+use tracing_subscriber::filter::{EnvFilter, LevelFilter};
+use tracing_subscriber::layer::SubscriberExt;
 use tracing_subscriber::util::SubscriberInitExt;
-use tracing_subscriber::{Layer, layer::SubscriberExt};
+use windows::Win32::Foundation::{HANDLE, NTSTATUS};
+use windows::Win32::System::Diagnostics::Debug::{
+    CONTEXT, CommentStreamW, EXCEPTION_POINTERS, EXCEPTION_RECORD, MINIDUMP_EXCEPTION_INFORMATION,
+    MINIDUMP_TYPE, MINIDUMP_USER_STREAM, MINIDUMP_USER_STREAM_INFORMATION, MiniDumpNormal,
+    MiniDumpWithThreadInfo, MiniDumpWriteDump, OutputDebugStringW, RtlCaptureContext,
+};
+use windows::Win32::System::Threading::{
+    GetCurrentProcess, GetCurrentProcessId, GetCurrentThreadId,
+};
+
+use crate::config::{self, LogSettings};
+use crate::pipeline::ResolvedText;
 
 use hudhook::windows::Win32::UI::WindowsAndMessaging::{
     MB_ICONERROR, MB_OK, MB_TASKMODAL, MessageBoxW,
 };
 use hudhook::windows::core::PCWSTR;
 
+/// Window [`LogThrottle`] counts lines in.
+const THROTTLE_WINDOW: Duration = Duration::from_secs(1);
+
+/// Rate limits a log call site on a hot path, such as a detour that runs for every draw.
+///
+/// At most `max_per_window` lines are let through per [`THROTTLE_WINDOW`], and a message
+/// identical to the last one let through waits for the next window. The number of lines held
+/// back is reported with the next line that gets through. Use it via [`debug_throttled`].
+pub struct LogThrottle {
+    max_per_window: u32,
+    state: Mutex<ThrottleState>,
+}
+
+struct ThrottleState {
+    window_start: Option<Instant>,
+    emitted: u32,
+    suppressed: u64,
+    last_message: u64,
+}
+
+impl LogThrottle {
+    pub const fn new(max_per_window: u32) -> Self {
+        Self {
+            max_per_window,
+            state: Mutex::new(ThrottleState {
+                window_start: None,
+                emitted: 0,
+                suppressed: 0,
+                last_message: 0,
+            }),
+        }
+    }
+
+    /// Whether `message` should be logged, along with how many lines were suppressed since the
+    /// last one that was.
+    pub fn admit(&self, message: &str) -> Option<u64> {
+        let mut hasher = DefaultHasher::new();
+        message.hash(&mut hasher);
+        let message_hash = hasher.finish();
+
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap();
+        let new_window = state
+            .window_start
+            .is_none_or(|start| now.duration_since(start) >= THROTTLE_WINDOW);
+        if new_window {
+            state.window_start = Some(now);
+            state.emitted = 0;
+        }
+
+        let repeated = !new_window && message_hash == state.last_message;
+        if repeated || state.emitted >= self.max_per_window {
+            state.suppressed += 1;
+            return None;
+        }
+
+        state.emitted += 1;
+        state.last_message = message_hash;
+        Some(std::mem::take(&mut state.suppressed))
+    }
+}
+
+/// `tracing::debug!` rate limited through a [`LogThrottle`]. The message isn't even formatted
+/// unless debug logging is enabled.
+///
+/// Compiled out entirely without the `hot-logging` feature.
+#[cfg(feature = "hot-logging")]
+macro_rules! debug_throttled {
+    ($throttle:expr, $($arg:tt)+) => {
+        if tracing::enabled!(tracing::Level::DEBUG) {
+            let message = format!($($arg)+);
+            match $throttle.admit(&message) {
+                Some(0) => tracing::debug!("{}", message),
+                Some(suppressed) => tracing::debug!("{} ...(suppressed {})", message, suppressed),
+                None => {}
+            }
+        }
+    };
+}
+#[cfg(not(feature = "hot-logging"))]
+macro_rules! debug_throttled {
+    ($throttle:expr, $($arg:tt)+) => {
+        // Keeps the throttle in use without evaluating anything
+        if false {
+            let _ = $throttle.admit("");
+        }
+    };
+}
+pub(crate) use debug_throttled;
+
+/// Enters a debug span named `$name` with a `mode` field, for filtering hot-path logs by
+/// coordinate mode. Without the `hot-logging` feature no span is created.
+#[cfg(feature = "hot-logging")]
+macro_rules! hot_span {
+    ($name:literal, $mode:expr) => {
+        tracing::debug_span!($name, mode = ?$mode).entered()
+    };
+}
+#[cfg(not(feature = "hot-logging"))]
+macro_rules! hot_span {
+    ($name:literal, $mode:expr) => {
+        tracing::Span::none().entered()
+    };
+}
+pub(crate) use hot_span;
+
 pub fn show_error_message_box(message: String, title: &str) {
     let mut message_utf16: Vec<u16> = message.encode_utf16().collect();
     message_utf16.push(0);
@@ -24,8 +156,8 @@ pub fn show_error_message_box(message: String, title: &str) {
     }
 }
 
-pub fn custom_panic_hook(panic_info: &std::panic::PanicHookInfo) {
-    let message;
+pub fn custom_panic_hook(panic_info: &std::panic::PanicHookInfo, dump_dir: &Path) {
+    let mut message;
     let reason = panic_info.payload().downcast_ref::<&str>();
 
     if let Some(location) = panic_info.location() {
@@ -42,21 +174,287 @@ pub fn custom_panic_hook(panic_info: &std::panic::PanicHookInfo) {
         );
     }
 
+    if config::try_current().is_some_and(|config| config.crash_dumps) {
+        match write_minidump(dump_dir, &message) {
+            Ok(path) => message = format!("{message}\nMinidump written to {}", path.display()),
+            Err(e) => message = format!("{message}\nFailed to write a minidump: {e}"),
+        }
+    }
+
     show_error_message_box(message, "Debug Text View Error");
     panic_hook(panic_info);
     std::process::abort();
 }
 
-pub fn setup_logging() {
+/// Made-up exception code the minidump's exception record carries, since a panic isn't a
+/// structured exception. `0xE...` marks it as a customer code.
+const PANIC_EXCEPTION_CODE: NTSTATUS = NTSTATUS(0xE0_52_53_54_u32 as i32);
+
+/// Writes a minidump of this process to `dir`, with the calling thread recorded as the faulting
+/// one and `comment` in the dump's comment stream.
+fn write_minidump(dir: &Path, comment: &str) -> io::Result<PathBuf> {
+    let timestamp = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let path = dir.join(format!("textrender-{timestamp}.dmp"));
+    let file = File::create(&path)?;
+
+    let mut comment_utf16: Vec<u16> = comment.encode_utf16().collect();
+    comment_utf16.push(0);
+    let mut comment_stream = MINIDUMP_USER_STREAM {
+        Type: CommentStreamW.0 as u32,
+        BufferSize: (comment_utf16.len() * size_of::<u16>()) as u32,
+        Buffer: comment_utf16.as_mut_ptr().cast(),
+    };
+    let user_streams = MINIDUMP_USER_STREAM_INFORMATION {
+        UserStreamCount: 1,
+        UserStreamArray: &mut comment_stream,
+    };
+
+    let mut context = CONTEXT::default();
+    unsafe { RtlCaptureContext(&mut context) };
+    let mut record = EXCEPTION_RECORD {
+        ExceptionCode: PANIC_EXCEPTION_CODE,
+        ExceptionAddress: context.Rip as *mut c_void,
+        ..Default::default()
+    };
+    let mut pointers = EXCEPTION_POINTERS {
+        ExceptionRecord: &mut record,
+        ContextRecord: &mut context,
+    };
+    let exception = MINIDUMP_EXCEPTION_INFORMATION {
+        ThreadId: unsafe { GetCurrentThreadId() },
+        ExceptionPointers: &mut pointers,
+        ClientPointers: false.into(),
+    };
+
+    unsafe {
+        MiniDumpWriteDump(
+            GetCurrentProcess(),
+            GetCurrentProcessId(),
+            HANDLE(file.as_raw_handle() as isize),
+            MINIDUMP_TYPE(MiniDumpNormal.0 | MiniDumpWithThreadInfo.0),
+            Some(&exception),
+            Some(&user_streams),
+            None,
+        )
+    }
+    .map_err(io::Error::other)?;
+
+    Ok(path)
+}
+
+/// Keeps the log file's background writer alive for as long as the DLL is loaded.
+static LOG_FILE_GUARD: OnceLock<WorkerGuard> = OnceLock::new();
+
+pub fn setup_logging(settings: &LogSettings) {
     // Try to attach to the parent console if it exists
     unsafe { AttachConsole(ATTACH_PARENT_PROCESS) }.ok();
 
-    let filter = tracing_subscriber::filter::EnvFilter::from_default_env()
-        .add_directive(tracing_subscriber::filter::LevelFilter::DEBUG.into());
+    let level = settings.level.parse().unwrap_or_else(|_| {
+        debug_output(&format!(
+            "textrender: unknown log level {:?}, using debug",
+            settings.level
+        ));
+        LevelFilter::DEBUG
+    });
+    let filter = EnvFilter::from_default_env().add_directive(level.into());
 
     let stdout_log = tracing_subscriber::fmt::layer().pretty();
+    let file_log = settings
+        .path
+        .as_deref()
+        .and_then(open_log_file)
+        .map(|file| {
+            let (writer, guard) = tracing_appender::non_blocking(file);
+            let _ = LOG_FILE_GUARD.set(guard);
+            tracing_subscriber::fmt::layer()
+                .with_ansi(false)
+                .with_writer(writer)
+        });
+    let debugger_log = settings.debugger.then(|| {
+        tracing_subscriber::fmt::layer()
+            .with_ansi(false)
+            .with_writer(|| DebuggerWriter)
+    });
 
     tracing_subscriber::registry()
-        .with(stdout_log.with_filter(filter))
+        .with(filter)
+        .with(stdout_log)
+        .with(file_log)
+        .with(debugger_log)
         .init();
 }
+
+/// Opens `path` for appending, or a file of the same name in the temp directory if that fails,
+/// e.g. because the game is installed somewhere read-only.
+///
+/// Logging isn't up yet, so failures are reported through [`debug_output`].
+fn open_log_file(path: &Path) -> Option<File> {
+    let open = |path: &Path| File::options().create(true).append(true).open(path);
+
+    let error = match open(path) {
+        Ok(file) => return Some(file),
+        Err(e) => e,
+    };
+    let fallback = std::env::temp_dir().join(path.file_name().unwrap_or("textrender.log".as_ref()));
+    match open(&fallback) {
+        Ok(file) => {
+            debug_output(&format!(
+                "textrender: can't write log file {} ({}), logging to {} instead",
+                path.display(),
+                error,
+                fallback.display()
+            ));
+            Some(file)
+        }
+        Err(fallback_error) => {
+            debug_output(&format!(
+                "textrender: can't write log file {} ({}) or {} ({})",
+                path.display(),
+                error,
+                fallback.display(),
+                fallback_error
+            ));
+            None
+        }
+    }
+}
+
+/// Sends `message` to an attached debugger, e.g. Visual Studio's output window or DebugView.
+fn debug_output(message: &str) {
+    let mut message_utf16: Vec<u16> = message.encode_utf16().collect();
+    message_utf16.extend(['\n' as u16, 0]);
+    unsafe { OutputDebugStringW(windows::core::PCWSTR(message_utf16.as_ptr())) };
+}
+
+/// Log writer that passes everything to [`debug_output`].
+struct DebuggerWriter;
+
+impl Write for DebuggerWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // Events arrive as whole lines, and debug_output puts the line break back
+        debug_output(String::from_utf8_lossy(buf).trim_end());
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// File that is moved aside to `<path>.1` once it would grow past `max_bytes`, replacing any
+/// previous backup.
+struct RotatingFile {
+    path: PathBuf,
+    max_bytes: u64,
+    file: File,
+    written: u64,
+}
+
+impl RotatingFile {
+    fn open(path: &Path, max_bytes: u64) -> io::Result<Self> {
+        let file = File::options().create(true).append(true).open(path)?;
+        let written = file.metadata()?.len();
+        Ok(Self {
+            path: path.to_owned(),
+            max_bytes,
+            file,
+            written,
+        })
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        let mut backup = self.path.clone().into_os_string();
+        backup.push(".1");
+
+        self.file.flush()?;
+        std::fs::rename(&self.path, backup)?;
+        self.file = File::create(&self.path)?;
+        self.written = 0;
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.written > 0 && self.written + buf.len() as u64 > self.max_bytes {
+            self.rotate()?;
+        }
+
+        let written = self.file.write(buf)?;
+        self.written += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+/// Appends every resolved label to a size-rotated text file.
+///
+/// Writes are handed to a background thread by `tracing-appender`, so the render loop never
+/// waits on the disk.
+#[derive(Default)]
+pub struct TextCapture {
+    /// Path the writer was opened for, to notice the config pointing somewhere else.
+    path: Option<PathBuf>,
+    writer: Option<(NonBlocking, WorkerGuard)>,
+    /// Set after failing to open `path` so a broken path doesn't spam the log every frame.
+    failed: bool,
+}
+
+impl TextCapture {
+    /// Appends `labels` to the capture at `path`. Does nothing when `path` is `None`.
+    pub fn record(&mut self, path: Option<&Path>, max_bytes: u64, labels: &[ResolvedText]) {
+        if path != self.path.as_deref() {
+            // Dropping the guard flushes whatever the previous file still had queued
+            self.writer = None;
+            self.path = path.map(Path::to_owned);
+            self.failed = false;
+        }
+        let Some(path) = path else {
+            return;
+        };
+        if self.failed || labels.is_empty() {
+            return;
+        }
+
+        if self.writer.is_none() {
+            match RotatingFile::open(path, max_bytes) {
+                Ok(file) => {
+                    tracing::info!("Capturing rendered text to {}", path.display());
+                    self.writer = Some(tracing_appender::non_blocking(file));
+                }
+                Err(e) => {
+                    tracing::error!("Failed to open text capture {}: {}", path.display(), e);
+                    self.failed = true;
+                    return;
+                }
+            }
+        }
+        let Some((writer, _)) = self.writer.as_mut() else {
+            return;
+        };
+
+        let timestamp = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs_f64();
+        let mut lines = String::new();
+        for label in labels {
+            let mode = label
+                .mode
+                .map_or_else(|| String::from("Screen"), |mode| format!("{mode:?}"));
+            let (x, y) = label.screen_pos;
+            lines.push_str(&format!(
+                "{timestamp:.3}\t{mode}\t{x:.1}\t{y:.1}\t{:?}\n",
+                label.text
+            ));
+        }
+        // The non-blocking writer drops lines rather than erroring when its buffer is full
+        let _ = writer.write_all(lines.as_bytes());
+    }
+}
@@ -0,0 +1,168 @@
+use std::time::{Duration, Instant};
+
+/// How often the latency summary and dropped command warnings are logged.
+const REPORT_INTERVAL: Duration = Duration::from_secs(5);
+/// Upper bounds of the latency histogram buckets; the last bucket catches everything above.
+const BUCKET_BOUNDS_MS: [f32; 4] = [1.0, 5.0, 16.7, 33.3];
+
+/// Tracks how long text waits in the queue between capture and being drawn.
+///
+/// High latency means the queue is backing up or the render thread is falling behind,
+/// as opposed to the game simply drawing a lot.
+pub struct LatencyStats {
+    count: u32,
+    total: Duration,
+    min: Duration,
+    max: Duration,
+    buckets: [u32; BUCKET_BOUNDS_MS.len() + 1],
+    last_report: Instant,
+}
+
+impl LatencyStats {
+    pub fn new() -> Self {
+        Self {
+            count: 0,
+            total: Duration::ZERO,
+            min: Duration::MAX,
+            max: Duration::ZERO,
+            buckets: [0; BUCKET_BOUNDS_MS.len() + 1],
+            last_report: Instant::now(),
+        }
+    }
+
+    pub fn record(&mut self, latency: Duration) {
+        self.count += 1;
+        self.total += latency;
+        self.min = self.min.min(latency);
+        self.max = self.max.max(latency);
+
+        let latency_ms = latency.as_secs_f32() * 1000.0;
+        let bucket = BUCKET_BOUNDS_MS
+            .iter()
+            .position(|&bound| latency_ms < bound)
+            .unwrap_or(BUCKET_BOUNDS_MS.len());
+        self.buckets[bucket] += 1;
+    }
+
+    /// Logs a summary once per [`REPORT_INTERVAL`] and starts a new one.
+    pub fn report_if_due(&mut self) {
+        if self.last_report.elapsed() < REPORT_INTERVAL {
+            return;
+        }
+
+        if self.count > 0 {
+            let average = self.total / self.count;
+            tracing::debug!(
+                "Text queue latency: {} samples, min {:.2}ms, avg {:.2}ms, max {:.2}ms, \
+                 buckets (<1/<5/<16.7/<33.3/>=33.3ms) {:?}",
+                self.count,
+                self.min.as_secs_f32() * 1000.0,
+                average.as_secs_f32() * 1000.0,
+                self.max.as_secs_f32() * 1000.0,
+                self.buckets
+            );
+        }
+        *self = Self::new();
+    }
+}
+
+/// Warns when commands were dropped because the queue was full or the frame went over its label
+/// budget, at most once per [`REPORT_INTERVAL`].
+pub struct DropReport {
+    /// Total dropped commands as of the last warning.
+    reported: u64,
+    last_report: Instant,
+}
+
+impl DropReport {
+    pub fn new() -> Self {
+        Self {
+            reported: 0,
+            last_report: Instant::now(),
+        }
+    }
+
+    /// `dropped` is the total number of commands dropped so far.
+    pub fn report_if_due(&mut self, dropped: u64) {
+        if dropped == self.reported || self.last_report.elapsed() < REPORT_INTERVAL {
+            return;
+        }
+
+        tracing::warn!(
+            "Dropped {} commands in the last {:.0?} ({} total). \
+             Consider raising queue_capacity or max_labels_per_frame",
+            dropped - self.reported,
+            self.last_report.elapsed(),
+            dropped
+        );
+        self.reported = dropped;
+        self.last_report = Instant::now();
+    }
+}
+
+/// Weight of the newest frame in [`FrameProfile`]'s moving averages.
+const PROFILE_SMOOTHING: f32 = 0.05;
+
+/// Stage of the render loop timed by [`FrameProfile`].
+#[derive(Debug, Clone, Copy)]
+pub enum Phase {
+    /// Popping captured commands off the queue.
+    Drain,
+    /// Projecting commands to screen positions and styling them.
+    Resolve,
+    /// Sinks, merging, retention, wrapping and ordering.
+    Layout,
+    /// Building the overlay window and submitting text to the draw list.
+    Submit,
+}
+
+impl Phase {
+    pub const ALL: [Phase; 4] = [Phase::Drain, Phase::Resolve, Phase::Layout, Phase::Submit];
+}
+
+/// Moving averages of how long each [`Phase`] of the render loop takes.
+///
+/// Phases are timed as laps: each [`FrameProfile::lap`] charges the time since the previous one
+/// to the given phase. While disabled, nothing is timed at all.
+pub struct FrameProfile {
+    enabled: bool,
+    last_lap: Option<Instant>,
+    averages_ms: [f32; Phase::ALL.len()],
+}
+
+impl FrameProfile {
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            last_lap: None,
+            averages_ms: [0.0; Phase::ALL.len()],
+        }
+    }
+
+    /// Starts timing a frame, or stops profiling altogether when `enabled` is false.
+    pub fn begin_frame(&mut self, enabled: bool) {
+        if enabled != self.enabled {
+            self.averages_ms = [0.0; Phase::ALL.len()];
+        }
+        self.enabled = enabled;
+        self.last_lap = enabled.then(Instant::now);
+    }
+
+    /// Charges the time since the last lap to `phase`.
+    pub fn lap(&mut self, phase: Phase) {
+        let Some(last_lap) = self.last_lap else {
+            return;
+        };
+
+        let now = Instant::now();
+        let elapsed_ms = now.duration_since(last_lap).as_secs_f32() * 1000.0;
+        let average = &mut self.averages_ms[phase as usize];
+        *average += (elapsed_ms - *average) * PROFILE_SMOOTHING;
+        self.last_lap = Some(now);
+    }
+
+    /// Average milliseconds spent in each of [`Phase::ALL`], or `None` while disabled.
+    pub fn averages_ms(&self) -> Option<[f32; Phase::ALL.len()]> {
+        self.enabled.then_some(self.averages_ms)
+    }
+}
@@ -0,0 +1,566 @@
+//! Resolves queued draw commands into positioned labels.
+//!
+//! Nothing in here touches ImGui or live game memory, so recorded frames can be pushed
+//! through exactly the same steps offline.
+
+use std::{
+    collections::{HashSet, hash_map::DefaultHasher},
+    hash::{Hash, Hasher},
+    str::FromStr,
+};
+
+use nalgebra::Vector3;
+use serde::{Deserialize, Serialize};
+
+use crate::command::{CoordMode, DrawCommand};
+use crate::config::Config;
+use crate::projection::{self, BehindCamera, CameraBasis};
+use crate::{pool, text};
+
+/// Corner screen-space positions are measured from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ScreenOrigin {
+    /// Y grows downwards, as the game lays out its own text.
+    #[default]
+    TopLeft,
+    /// Y grows upwards from the bottom edge.
+    BottomLeft,
+}
+
+impl FromStr for ScreenOrigin {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "top-left" => Ok(Self::TopLeft),
+            "bottom-left" => Ok(Self::BottomLeft),
+            _ => Err(()),
+        }
+    }
+}
+
+impl ScreenOrigin {
+    /// Converts `y` measured from this origin in a space `height` units tall to top-left based.
+    fn to_top_left(self, y: f32, height: f32) -> f32 {
+        match self {
+            Self::TopLeft => y,
+            Self::BottomLeft => height - y,
+        }
+    }
+}
+
+/// Game state the commands of one frame are resolved against.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FrameContext {
+    /// Size of the ImGui display in pixels.
+    pub window_size: (f32, f32),
+    /// Resolution the game lays out screen-space text at.
+    pub window_resolution: (f32, f32),
+    pub camera: Option<CameraBasis>,
+    /// EzDraw text colour normalized to [0.0-1.0].
+    pub text_color: [f32; 4],
+    /// Pixel size the game wants text drawn at.
+    pub font_size: f32,
+}
+
+/// A label ready to be drawn.
+#[derive(Debug, Clone)]
+pub struct ResolvedText {
+    pub text: String,
+    /// `None` for text submitted directly in screen pixels.
+    pub mode: Option<CoordMode>,
+    pub raw_pos: (f32, f32, f32),
+    pub screen_pos: (f32, f32),
+    /// Distance in front of the camera, for world-space text.
+    pub depth: Option<f32>,
+    pub color: [f32; 4],
+    pub font_size: f32,
+}
+
+/// A line or rectangle ready to be drawn, in screen pixels.
+#[derive(Debug, Clone)]
+pub struct ResolvedShape {
+    pub kind: ShapeKind,
+    pub from: (f32, f32),
+    pub to: (f32, f32),
+    pub color: [f32; 4],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShapeKind {
+    Line,
+    Rect { filled: bool },
+}
+
+/// Turns a frame's commands into labels and shapes.
+#[derive(Debug, Default)]
+pub struct Pipeline {
+    /// Keys of the labels resolved so far this frame, kept to avoid reallocating every frame.
+    seen: HashSet<u64>,
+}
+
+impl Pipeline {
+    /// Resolves the text among `commands`.
+    ///
+    /// The game sometimes draws the same label twice in a frame, e.g. through both hooks, which
+    /// would double up its outline and opacity. Only the first label with a given text, mode and
+    /// whole pixel position is kept.
+    pub fn process_commands(
+        &mut self,
+        commands: impl IntoIterator<Item = DrawCommand>,
+        frame: &FrameContext,
+        config: &Config,
+    ) -> Vec<ResolvedText> {
+        self.seen.clear();
+        let mut labels = Vec::new();
+        for command in commands {
+            let Some(label) = self.process(command, frame, config) else {
+                continue;
+            };
+            if self.seen.insert(dedup_key(&label)) {
+                labels.push(label);
+            } else {
+                pool::recycle(label.text);
+            }
+        }
+        labels
+    }
+
+    /// Resolves the shapes among `commands`; text is left to [`Pipeline::process_commands`].
+    pub fn process_shapes(
+        &mut self,
+        commands: &[DrawCommand],
+        frame: &FrameContext,
+        config: &Config,
+    ) -> Vec<ResolvedShape> {
+        commands
+            .iter()
+            .filter_map(|command| {
+                let (kind, mode, from, to) = match *command {
+                    DrawCommand::Line(mode, from, to) => (ShapeKind::Line, mode, from, to),
+                    DrawCommand::Rect(mode, from, to, filled) => {
+                        (ShapeKind::Rect { filled }, mode, from, to)
+                    }
+                    DrawCommand::Text(..) | DrawCommand::ScreenText(..) => return None,
+                };
+                if !config.enabled_modes.contains(&mode) {
+                    return None;
+                }
+
+                // A shape only makes sense with both ends on screen
+                Some(ResolvedShape {
+                    kind,
+                    from: resolve_position(mode, from, frame, config, BehindCamera::Drop)?,
+                    to: resolve_position(mode, to, frame, config, BehindCamera::Drop)?,
+                    color: config.text_color(Some(mode), frame.text_color),
+                })
+            })
+            .collect()
+    }
+
+    /// Resolves a text command into a label. Shapes are skipped, see [`Pipeline::process_shapes`].
+    pub fn process(
+        &mut self,
+        command: DrawCommand,
+        frame: &FrameContext,
+        config: &Config,
+    ) -> Option<ResolvedText> {
+        match command {
+            DrawCommand::Text(text, x, y, z, mode, offset) => {
+                if !config.enabled_modes.contains(&mode) {
+                    return None;
+                }
+                if mode.is_world_space() && beyond_render_distance((x, y, z), frame, config) {
+                    return None;
+                }
+
+                let (mut new_x, mut new_y) =
+                    resolve_position(mode, (x, y, z), frame, config, config.behind_camera)?;
+                if !mode.is_world_space() && config.screen_edge_padding > 0.0 {
+                    (new_x, new_y) = pad_from_edges(
+                        (new_x, new_y),
+                        frame.window_size,
+                        config.screen_edge_padding,
+                    );
+                }
+
+                // Each text carries its own offset so it can never leak onto the next one
+                let (offset_x, offset_y) = offset.unwrap_or_default();
+                let screen_pos = (new_x + offset_x, new_y + offset_y);
+
+                let depth = frame
+                    .camera
+                    .as_ref()
+                    .filter(|_| mode.is_world_space())
+                    .map(|camera| projection::view_depth(camera, Vector3::new(x, y, z)));
+                let mut color = config.text_color(Some(mode), frame.text_color);
+                if let Some(depth) = depth {
+                    // The tint replaces the colour, but opacity settings still apply on top
+                    if let Some([r, g, b, a]) = config.distance_tint.color(depth) {
+                        color = [r, g, b, color[3] * a];
+                    }
+                    color[3] *= config.distance_fade.alpha(depth);
+                }
+
+                Some(ResolvedText {
+                    text: prepare_text(text, config),
+                    mode: Some(mode),
+                    raw_pos: (x, y, z),
+                    screen_pos,
+                    depth,
+                    color,
+                    font_size: frame.font_size,
+                })
+            }
+            DrawCommand::ScreenText(text, x, y) => Some(ResolvedText {
+                text: prepare_text(text, config),
+                mode: None,
+                raw_pos: (x, y, 0.0),
+                screen_pos: (x, y),
+                depth: None,
+                color: config.text_color(None, frame.text_color),
+                font_size: frame.font_size,
+            }),
+            DrawCommand::Line(..) | DrawCommand::Rect(..) => None,
+        }
+    }
+}
+
+/// Cuts `labels` down to at most `max` to bound the cost of a frame, returning how many were
+/// dropped. `max == 0` disables the cap.
+///
+/// Screen-space text is kept first, then world-space text nearest first. When any are dropped,
+/// `labels` is left in that order: screen-space labels in submission order followed by
+/// world-space labels sorted by depth.
+pub fn apply_label_budget(labels: &mut Vec<ResolvedText>, max: usize) -> usize {
+    if max == 0 || labels.len() <= max {
+        return 0;
+    }
+
+    // Stable, so screen-space labels keep their submission order
+    labels.sort_by(|a, b| match (a.depth, b.depth) {
+        (None, None) => std::cmp::Ordering::Equal,
+        (None, Some(_)) => std::cmp::Ordering::Less,
+        (Some(_), None) => std::cmp::Ordering::Greater,
+        (Some(a), Some(b)) => a.total_cmp(&b),
+    });
+    let dropped = labels.len() - max;
+    for label in labels.drain(max..) {
+        pool::recycle(label.text);
+    }
+    dropped
+}
+
+/// Concatenates consecutive labels the game drew as fragments of a single line.
+///
+/// A fragment joins the previous label when it shares its baseline and style and starts where
+/// that label ends, both within `tolerance` pixels. `measure_width` returns the drawn width of
+/// a label in screen pixels.
+pub fn merge_fragments(
+    labels: Vec<ResolvedText>,
+    tolerance: f32,
+    measure_width: impl Fn(&ResolvedText) -> f32,
+) -> Vec<ResolvedText> {
+    let mut merged: Vec<ResolvedText> = Vec::with_capacity(labels.len());
+    // Screen x where each merged label currently ends
+    let mut ends: Vec<f32> = Vec::with_capacity(labels.len());
+
+    for label in labels {
+        let width = measure_width(&label);
+
+        if let (Some(previous), Some(end)) = (merged.last_mut(), ends.last_mut()) {
+            let continues_line = previous.mode == label.mode
+                && previous.color == label.color
+                && previous.font_size == label.font_size
+                && (previous.screen_pos.1 - label.screen_pos.1).abs() <= tolerance
+                && (*end - label.screen_pos.0).abs() <= tolerance;
+
+            if continues_line {
+                previous.text.push_str(&label.text);
+                *end = label.screen_pos.0 + width;
+                continue;
+            }
+        }
+
+        ends.push(label.screen_pos.0 + width);
+        merged.push(label);
+    }
+
+    merged
+}
+
+/// Fills `order` with indices into `labels` in the order they should be drawn.
+///
+/// World-space labels come first, farthest first so nearer text lands on top of it. Everything
+/// else follows in submission order. `order` is cleared first so it can be reused across frames.
+pub fn draw_order(labels: &[ResolvedText], order: &mut Vec<usize>) {
+    order.clear();
+    order.extend((0..labels.len()).filter(|&i| labels[i].depth.is_some()));
+    // Stable, so labels at the same depth keep their submission order
+    order.sort_by(|&a, &b| {
+        let depth = |i: usize| labels[i].depth.unwrap_or_default();
+        depth(b).total_cmp(&depth(a))
+    });
+    order.extend((0..labels.len()).filter(|&i| labels[i].depth.is_none()));
+}
+
+/// Labels keep their text in logical order; right-to-left text is only reordered for drawing,
+/// once it has been wrapped.
+fn prepare_text(text: String, config: &Config) -> String {
+    let text = text::transform(text, config.text_case, config.normalize_nfc);
+    text::truncate(text, config.max_text_chars)
+}
+
+/// Whether world position `pos` is farther from the camera than [`Config::max_render_distance`].
+fn beyond_render_distance(
+    (x, y, z): (f32, f32, f32),
+    frame: &FrameContext,
+    config: &Config,
+) -> bool {
+    let Some(camera) = frame.camera.as_ref() else {
+        return false;
+    };
+    config.max_render_distance > 0.0
+        && (Vector3::new(x, y, z) - camera.position).norm() > config.max_render_distance
+}
+
+/// Moves `pos` at least `padding` pixels away from every edge of a screen of `screen_size`.
+fn pad_from_edges(pos: (f32, f32), screen_size: (f32, f32), padding: f32) -> (f32, f32) {
+    let pad = |value: f32, size: f32| value.clamp(padding, (size - padding).max(padding));
+    (pad(pos.0, screen_size.0), pad(pos.1, screen_size.1))
+}
+
+/// Identifies labels that would be drawn identically within a frame.
+fn dedup_key(label: &ResolvedText) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    label.text.hash(&mut hasher);
+    label.mode.hash(&mut hasher);
+    (label.screen_pos.0.round() as i32).hash(&mut hasher);
+    (label.screen_pos.1.round() as i32).hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Maps a position in `mode` to screen pixels, or `None` if it can't be placed on screen.
+///
+/// Screen-space positions are measured from [`Config::screen_origin`].
+fn resolve_position(
+    mode: CoordMode,
+    (x, y, z): (f32, f32, f32),
+    frame: &FrameContext,
+    config: &Config,
+    behind_camera: BehindCamera,
+) -> Option<(f32, f32)> {
+    let origin = config.screen_origin;
+    let (new_x, new_y) = match mode {
+        CoordMode::HavokPosition2 | CoordMode::HavokPosition3 => {
+            let camera = frame.camera.as_ref()?;
+            projection::project(
+                camera,
+                Vector3::new(x, y, z),
+                frame.window_size,
+                behind_camera,
+            )?
+        }
+        CoordMode::ScreenSpace0 | CoordMode::ScreenSpace1 => {
+            let resolution = frame.window_resolution;
+            let size = frame.window_size;
+            // Without a resolution to scale from, the pixels are taken as they are
+            let scale = |size: f32, resolution: f32| match size / resolution {
+                scale if scale.is_finite() => scale,
+                _ => 1.0,
+            };
+            let scale_x = scale(size.0, resolution.0);
+            let scale_y = scale(size.1, resolution.1);
+            let y = origin.to_top_left(y, resolution.1);
+            (x * scale_x, y * scale_y)
+        }
+        CoordMode::Normalized4k => {
+            let screen_size = frame.window_resolution;
+            let diff_x: f32 = screen_size.0 / 3840.0;
+            let diff_y: f32 = screen_size.1 / 2160.0;
+            let y = origin.to_top_left(y, 2160.0);
+            (x * diff_x, y * diff_y)
+        }
+        CoordMode::Normalized1080p => {
+            let screen_size = frame.window_resolution;
+            let diff_x: f32 = screen_size.0 / 1920.0;
+            let diff_y: f32 = screen_size.1 / 1080.0;
+            let y = origin.to_top_left(y, 1080.0);
+            (x * diff_x, y * diff_y)
+        }
+    };
+
+    (new_x.is_finite() && new_y.is_finite()).then_some((new_x, new_y))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FULL_HD: (f32, f32) = (1920.0, 1080.0);
+
+    /// Looking down +Z from the origin with a 90 degree vertical field of view.
+    fn camera() -> CameraBasis {
+        CameraBasis {
+            right: Vector3::new(1.0, 0.0, 0.0),
+            up: Vector3::new(0.0, 1.0, 0.0),
+            forward: Vector3::new(0.0, 0.0, 1.0),
+            position: Vector3::new(0.0, 0.0, 0.0),
+            fov: std::f32::consts::FRAC_PI_2,
+            aspect_ratio: 16.0 / 9.0,
+        }
+    }
+
+    fn frame(window_size: (f32, f32), window_resolution: (f32, f32)) -> FrameContext {
+        FrameContext {
+            window_size,
+            window_resolution,
+            camera: Some(camera()),
+            text_color: [1.0; 4],
+            font_size: 24.0,
+        }
+    }
+
+    fn text(x: f32, y: f32, z: f32, mode: CoordMode) -> DrawCommand {
+        DrawCommand::Text("label".to_owned(), x, y, z, mode, None)
+    }
+
+    #[test]
+    fn zero_window_size_never_resolves_to_nan_or_infinity() {
+        let config = Config::default();
+        for (window_size, window_resolution) in [
+            ((0.0, 0.0), FULL_HD),
+            (FULL_HD, (0.0, 0.0)),
+            ((0.0, 0.0), (0.0, 0.0)),
+        ] {
+            let frame = frame(window_size, window_resolution);
+            for mode in CoordMode::ALL {
+                let label =
+                    Pipeline::default().process(text(100.0, 100.0, 10.0, mode), &frame, &config);
+                let (x, y) = label
+                    .unwrap_or_else(|| {
+                        panic!("{mode:?} in {window_size:?} at {window_resolution:?} was dropped")
+                    })
+                    .screen_pos;
+                assert!(
+                    x.is_finite() && y.is_finite(),
+                    "{mode:?} in {window_size:?} at {window_resolution:?} gave ({x}, {y})"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn duplicate_commands_resolve_to_one_label() {
+        let commands = [
+            text(100.0, 200.0, 0.0, CoordMode::Normalized1080p),
+            text(100.0, 200.0, 0.0, CoordMode::Normalized1080p),
+            text(100.2, 199.9, 0.0, CoordMode::Normalized1080p),
+            text(300.0, 200.0, 0.0, CoordMode::Normalized1080p),
+        ];
+        let labels = Pipeline::default().process_commands(
+            commands,
+            &frame(FULL_HD, FULL_HD),
+            &Config::default(),
+        );
+
+        let positions: Vec<_> = labels.iter().map(|label| label.screen_pos).collect();
+        assert_eq!(positions, [(100.0, 200.0), (300.0, 200.0)]);
+    }
+
+    #[test]
+    fn edge_padding_moves_screen_text_in_and_leaves_world_text() {
+        let padded = Config {
+            screen_edge_padding: 16.0,
+            ..Config::default()
+        };
+        let frame = frame(FULL_HD, FULL_HD);
+        let mut pipeline = Pipeline::default();
+
+        let label = pipeline
+            .process(
+                text(0.0, 540.0, 0.0, CoordMode::Normalized1080p),
+                &frame,
+                &padded,
+            )
+            .unwrap();
+        assert_eq!(label.screen_pos, (16.0, 540.0));
+        assert_eq!(
+            pad_from_edges((1920.0, 1080.0), FULL_HD, 16.0),
+            (1904.0, 1064.0)
+        );
+
+        // Projects a few pixels from the left edge
+        let world = || text(-17.7, 0.0, 10.0, CoordMode::HavokPosition3);
+        let unpadded = pipeline
+            .process(world(), &frame, &Config::default())
+            .unwrap();
+        assert!(unpadded.screen_pos.0 < 16.0);
+        let label = pipeline.process(world(), &frame, &padded).unwrap();
+        assert_eq!(label.screen_pos, unpadded.screen_pos);
+    }
+
+    #[test]
+    fn bottom_left_origin_flips_y() {
+        assert_eq!(ScreenOrigin::BottomLeft.to_top_left(100.0, 1080.0), 980.0);
+        assert_eq!(ScreenOrigin::TopLeft.to_top_left(100.0, 1080.0), 100.0);
+
+        let config = Config {
+            screen_origin: ScreenOrigin::BottomLeft,
+            ..Config::default()
+        };
+        let uhd = (3840.0, 2160.0);
+        let label = Pipeline::default()
+            .process(
+                text(480.0, 100.0, 0.0, CoordMode::Normalized1080p),
+                &frame(uhd, uhd),
+                &config,
+            )
+            .unwrap();
+        assert_eq!(label.screen_pos, (960.0, 1960.0));
+    }
+
+    #[test]
+    fn long_text_is_cut_before_layout() {
+        let config = Config::default();
+        let command = DrawCommand::Text(
+            "a".repeat(10_000),
+            960.0,
+            540.0,
+            0.0,
+            CoordMode::Normalized1080p,
+            None,
+        );
+        let label = Pipeline::default()
+            .process(command, &frame(FULL_HD, FULL_HD), &config)
+            .unwrap();
+        assert_eq!(label.text.chars().count(), config.max_text_chars + 3);
+    }
+
+    #[test]
+    fn each_text_gets_its_own_offset() {
+        let with_offset = |text: &str, offset| {
+            DrawCommand::Text(
+                text.to_owned(),
+                960.0,
+                540.0,
+                0.0,
+                CoordMode::Normalized1080p,
+                offset,
+            )
+        };
+        let labels = Pipeline::default().process_commands(
+            [
+                with_offset("first", Some((10.0, 20.0))),
+                with_offset("second", Some((-30.0, 40.0))),
+                with_offset("third", None),
+            ],
+            &frame(FULL_HD, FULL_HD),
+            &Config::default(),
+        );
+
+        let positions: Vec<_> = labels.iter().map(|label| label.screen_pos).collect();
+        assert_eq!(positions, [(970.0, 560.0), (930.0, 580.0), (960.0, 540.0)]);
+    }
+}
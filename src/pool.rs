@@ -0,0 +1,119 @@
+//! Recycled `String` buffers for captured text.
+//!
+//! Every intercepted draw call needs a string, so instead of allocating a fresh one each time
+//! the detours take a buffer from here and the render loop hands it back once the label it
+//! ended up in has been drawn.
+
+use std::sync::LazyLock;
+
+use crossbeam_queue::ArrayQueue;
+
+/// Most idle buffers kept around; anything returned beyond this is freed.
+const POOL_CAPACITY: usize = 4096;
+/// Buffers that grew larger than this are freed rather than kept, so one huge string doesn't
+/// pin its memory for the rest of the session.
+const MAX_POOLED_BYTES: usize = 1024;
+
+static POOL: LazyLock<Pool> = LazyLock::new(|| Pool::new(POOL_CAPACITY));
+
+/// Idle buffers waiting to be reused.
+struct Pool {
+    buffers: ArrayQueue<String>,
+}
+
+impl Pool {
+    fn new(capacity: usize) -> Self {
+        Self {
+            buffers: ArrayQueue::new(capacity),
+        }
+    }
+
+    fn take(&self) -> String {
+        self.buffers.pop().unwrap_or_default()
+    }
+
+    fn recycle(&self, mut text: String) {
+        if text.capacity() == 0 || text.capacity() > MAX_POOLED_BYTES {
+            return;
+        }
+
+        text.clear();
+        let _ = self.buffers.push(text);
+    }
+}
+
+/// An empty string, reusing a recycled buffer when one is available.
+pub fn take() -> String {
+    POOL.take()
+}
+
+/// Returns `text`'s buffer to the pool.
+pub fn recycle(text: String) {
+    POOL.recycle(text);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        alloc::{GlobalAlloc, Layout, System},
+        cell::Cell,
+    };
+
+    use super::*;
+
+    /// Counts allocations made on each thread, so tests running alongside don't skew the count.
+    struct CountingAllocator;
+
+    thread_local! {
+        static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+    }
+
+    unsafe impl GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            ALLOCATIONS.with(|count| count.set(count.get() + 1));
+            unsafe { System.alloc(layout) }
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            unsafe { System.dealloc(ptr, layout) }
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+    const CALLS: usize = 1000;
+
+    /// Allocations made on this thread while running `f`.
+    fn allocations(f: impl FnOnce()) -> usize {
+        let before = ALLOCATIONS.with(Cell::get);
+        f();
+        ALLOCATIONS.with(Cell::get) - before
+    }
+
+    #[test]
+    fn recycled_buffers_are_not_reallocated() {
+        let fresh = allocations(|| {
+            for _ in 0..CALLS {
+                let mut text = String::new();
+                text.push_str("Lost Grace Discovered");
+                std::hint::black_box(text);
+            }
+        });
+        assert_eq!(fresh, CALLS);
+
+        let pool = Pool::new(16);
+        let mut text = pool.take();
+        text.push_str("Lost Grace Discovered");
+        pool.recycle(text);
+
+        let pooled = allocations(|| {
+            for _ in 0..CALLS {
+                let mut text = pool.take();
+                text.push_str("Lost Grace Discovered");
+                pool.recycle(text);
+            }
+        });
+        assert_eq!(pooled, 0);
+    }
+}
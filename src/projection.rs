@@ -0,0 +1,282 @@
+use eldenring::cs::CSCamera;
+use fromsoftware_shared::FromStatic;
+use nalgebra::Vector3;
+use serde::{Deserialize, Serialize};
+
+/// Anything closer to the camera plane than this is treated as behind the camera.
+/// Dividing by a smaller depth only produces coordinates far outside the screen.
+const MIN_VIEW_DEPTH: f32 = 1.0e-4;
+
+/// What happens to world-space text behind the camera.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BehindCamera {
+    /// Skip the text.
+    #[default]
+    Drop,
+    /// Pin the text to the screen edge in the direction of the point, so e.g. a nameplate
+    /// doesn't flicker while its target crosses the camera plane.
+    Clamp,
+}
+
+impl std::str::FromStr for BehindCamera {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "drop" => Ok(Self::Drop),
+            "clamp" => Ok(Self::Clamp),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Snapshot of the camera basis used to project world-space text.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct CameraBasis {
+    pub right: Vector3<f32>,
+    pub up: Vector3<f32>,
+    pub forward: Vector3<f32>,
+    pub position: Vector3<f32>,
+    /// Vertical field of view in radians.
+    pub fov: f32,
+    /// Only used by [`project`] when the screen size has no usable aspect ratio.
+    pub aspect_ratio: f32,
+}
+
+/// Highest camera number [`CameraBasis::current`] accepts.
+pub const CAMERA_COUNT: u8 = 4;
+
+impl CameraBasis {
+    /// Basis of `CSCamera`'s `pers_cam_<index>`, numbered from 1. Out of range indices use the
+    /// first camera.
+    pub fn current(index: u8) -> Option<Self> {
+        let camera = unsafe { CSCamera::instance() }.ok()?;
+        let cam = match index {
+            2 => &camera.pers_cam_2,
+            3 => &camera.pers_cam_3,
+            4 => &camera.pers_cam_4,
+            _ => &camera.pers_cam_1,
+        };
+
+        let right = cam.right();
+        let up = cam.up();
+        let forward = cam.forward();
+        let position = cam.position();
+
+        Some(Self {
+            right: Vector3::new(right.0, right.1, right.2),
+            up: Vector3::new(up.0, up.1, up.2),
+            forward: Vector3::new(forward.0, forward.1, forward.2),
+            position: Vector3::new(position.0, position.1, position.2),
+            fov: cam.fov,
+            aspect_ratio: cam.aspect_ratio,
+        })
+    }
+}
+
+/// Flushes subnormal floats to zero so they don't propagate through the rest of the math.
+/// Subnormal arithmetic is very slow on some CPUs and never matters at pixel precision.
+#[inline]
+fn flush_denormal(value: f32) -> f32 {
+    if value.is_subnormal() { 0.0 } else { value }
+}
+
+/// Distance of `world_pos` in front of the camera along its forward axis.
+pub fn view_depth(camera: &CameraBasis, world_pos: Vector3<f32>) -> f32 {
+    let rel = (world_pos - camera.position).map(flush_denormal);
+    flush_denormal(camera.forward.dot(&rel))
+}
+
+/// Projects a Havok world position to screen pixels.
+///
+/// Returns `None` when the camera/geometry produce a result that isn't a usable finite
+/// coordinate, or the point is behind the camera and `behind` says to drop it.
+pub fn project(
+    camera: &CameraBasis,
+    world_pos: Vector3<f32>,
+    screen_size: (f32, f32),
+    behind: BehindCamera,
+) -> Option<(f32, f32)> {
+    let rel = (world_pos - camera.position).map(flush_denormal);
+
+    let z_cam = view_depth(camera, world_pos);
+    let is_behind = z_cam <= MIN_VIEW_DEPTH;
+    if !z_cam.is_finite() || (is_behind && behind == BehindCamera::Drop) {
+        return None;
+    }
+    let z_cam = z_cam.max(MIN_VIEW_DEPTH);
+
+    let x_cam = flush_denormal(camera.right.dot(&rel));
+    let y_cam = flush_denormal(camera.up.dot(&rel));
+
+    // The camera's own aspect ratio lags behind the window during resizes and mode switches,
+    // which on ultrawide displays drifts X off the game's placement. Use the size the NDC
+    // coordinates are actually mapped onto instead.
+    let aspect_ratio = match screen_size.0 / screen_size.1 {
+        aspect if aspect.is_finite() && aspect > 0.0 => aspect,
+        _ => camera.aspect_ratio,
+    };
+    let m11 = 1.0 / (0.5 * camera.fov).tan();
+    let m00 = m11 / aspect_ratio;
+    if !m11.is_finite() || !m00.is_finite() {
+        return None;
+    }
+
+    let mut ndc_x = flush_denormal(x_cam * m00 / z_cam);
+    let mut ndc_y = flush_denormal(y_cam * m11 / z_cam);
+
+    if is_behind {
+        // Move the point along the ray from the screen centre onto the edge, outwards as well
+        // as inwards, since text behind the camera is never inside the view
+        let extent = ndc_x.abs().max(ndc_y.abs());
+        if extent == 0.0 {
+            // Dead behind has no direction, so park it at the bottom
+            ndc_y = -1.0;
+        } else {
+            ndc_x /= extent;
+            ndc_y /= extent;
+        }
+    }
+
+    let screen_x = flush_denormal((ndc_x * 0.5 + 0.5) * screen_size.0);
+    let screen_y = flush_denormal((ndc_y * -0.5 + 0.5) * screen_size.1);
+
+    if !screen_x.is_finite() || !screen_y.is_finite() {
+        return None;
+    }
+
+    Some((screen_x, screen_y))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Looking down +Z with a 90 degree vertical field of view. Its own aspect ratio is left at
+    /// 16:9 whatever the screen, as during a resize.
+    fn camera() -> CameraBasis {
+        CameraBasis {
+            right: Vector3::new(1.0, 0.0, 0.0),
+            up: Vector3::new(0.0, 1.0, 0.0),
+            forward: Vector3::new(0.0, 0.0, 1.0),
+            position: Vector3::new(0.0, 0.0, 0.0),
+            fov: std::f32::consts::FRAC_PI_2,
+            aspect_ratio: 16.0 / 9.0,
+        }
+    }
+
+    #[test]
+    fn projection_follows_the_screen_aspect_ratio() {
+        let point = Vector3::new(2.0, 1.0, 10.0);
+        for width in [1920.0, 2520.0, 3840.0] {
+            let (x, y) = project(&camera(), point, (width, 1080.0), BehindCamera::Drop)
+                .expect("point in front of the camera");
+
+            // The field of view is vertical, so a point keeps its pixel distance from the
+            // centre however wide the screen is
+            assert!((x - (width * 0.5 + 108.0)).abs() < 1e-3, "x {x} at {width}");
+            assert!((y - 486.0).abs() < 1e-3, "y {y} at {width}");
+        }
+    }
+
+    #[test]
+    fn extreme_values_never_project_to_nan_or_infinity() {
+        let subnormal = f32::MIN_POSITIVE / 2.0;
+        let values = [
+            0.0,
+            subnormal,
+            -subnormal,
+            f32::MIN_POSITIVE,
+            1.0,
+            -1.0,
+            1.0e30,
+            -1.0e30,
+            f32::MAX,
+            f32::MIN,
+        ];
+
+        let mut cameras = vec![camera()];
+        for value in values {
+            cameras.push(CameraBasis {
+                position: Vector3::new(value, value, value),
+                ..camera()
+            });
+            cameras.push(CameraBasis {
+                forward: Vector3::new(subnormal, subnormal, value),
+                fov: value,
+                aspect_ratio: value,
+                ..camera()
+            });
+        }
+
+        for camera in &cameras {
+            for value in values {
+                for point in [
+                    Vector3::new(value, value, value),
+                    Vector3::new(subnormal, subnormal, value),
+                    Vector3::new(value, 0.0, 10.0),
+                ] {
+                    for screen_size in [(1920.0, 1080.0), (subnormal, subnormal), (0.0, 0.0)] {
+                        for behind in [BehindCamera::Drop, BehindCamera::Clamp] {
+                            if let Some((x, y)) = project(camera, point, screen_size, behind) {
+                                assert!(
+                                    x.is_finite() && y.is_finite(),
+                                    "{point:?} through {camera:?} onto {screen_size:?} gave ({x}, {y})"
+                                );
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        // Clamped text only goes missing when it lands too far out to be placed at all
+        for value in values.into_iter().filter(|value| value.abs() <= 1.0e30) {
+            for point in [
+                Vector3::new(value, value, value),
+                Vector3::new(subnormal, subnormal, value),
+                Vector3::new(value, 0.0, 10.0),
+            ] {
+                assert!(
+                    project(&camera(), point, (1920.0, 1080.0), BehindCamera::Clamp).is_some(),
+                    "{point:?} was dropped"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn points_in_view_project_onto_the_screen() {
+        for behind in [BehindCamera::Drop, BehindCamera::Clamp] {
+            assert_eq!(
+                project(
+                    &camera(),
+                    Vector3::new(1.0, 0.0, 10.0),
+                    (1920.0, 1080.0),
+                    behind
+                ),
+                Some((1014.0, 540.0))
+            );
+        }
+    }
+
+    #[test]
+    fn subnormal_inputs_are_flushed_to_zero() {
+        let subnormal = f32::MIN_POSITIVE / 2.0;
+        let point = Vector3::new(subnormal, subnormal, 10.0);
+        assert_eq!(
+            project(&camera(), point, (1920.0, 1080.0), BehindCamera::Drop),
+            Some((960.0, 540.0))
+        );
+        assert_eq!(
+            project(
+                &camera(),
+                Vector3::new(0.0, 0.0, 10.0),
+                (subnormal, subnormal),
+                BehindCamera::Drop
+            ),
+            Some((0.0, 0.0))
+        );
+    }
+}
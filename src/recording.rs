@@ -0,0 +1,102 @@
+//! JSONL recording of the draw command stream.
+//!
+//! Each line holds one frame: its number, counted from when recording started, the
+//! [`FrameContext`] it was resolved against and the commands drained from the queue that frame.
+//! Frames without any commands aren't written, so the numbers can skip. The `textrender-replay` binary feeds these files back
+//! through the [`Pipeline`](crate::pipeline::Pipeline).
+
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::command::DrawCommand;
+use crate::pipeline::FrameContext;
+
+#[derive(Debug, Deserialize)]
+pub struct RecordedFrame {
+    /// Missing from recordings made before frames were numbered.
+    #[serde(default)]
+    pub frame: Option<u64>,
+    pub context: FrameContext,
+    pub commands: Vec<DrawCommand>,
+}
+
+#[derive(Serialize)]
+struct FrameRecord<'a> {
+    frame: u64,
+    context: &'a FrameContext,
+    commands: &'a [DrawCommand],
+}
+
+impl RecordedFrame {
+    pub fn parse(line: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(line)
+    }
+}
+
+#[derive(Default)]
+pub struct Recorder {
+    writer: Option<BufWriter<File>>,
+    /// Set after an I/O error so a broken path doesn't spam the log every frame.
+    failed: bool,
+    /// Frames seen since recording started, written or not.
+    frames: u64,
+}
+
+impl Recorder {
+    /// Appends a frame to the recording at `path`. Does nothing when `path` is `None`.
+    pub fn record(
+        &mut self,
+        path: Option<&Path>,
+        context: &FrameContext,
+        commands: &[DrawCommand],
+    ) {
+        let Some(path) = path else {
+            return;
+        };
+        if self.failed {
+            return;
+        }
+        let frame = self.frames;
+        self.frames += 1;
+        if commands.is_empty() {
+            return;
+        }
+
+        if self.writer.is_none() {
+            match File::create(path) {
+                Ok(file) => {
+                    tracing::info!("Recording draw commands to {}", path.display());
+                    self.writer = Some(BufWriter::new(file));
+                }
+                Err(e) => {
+                    tracing::error!("Failed to create recording {}: {}", path.display(), e);
+                    self.failed = true;
+                    return;
+                }
+            }
+        }
+        let Some(writer) = self.writer.as_mut() else {
+            return;
+        };
+
+        let record = FrameRecord {
+            frame,
+            context,
+            commands,
+        };
+        let result = serde_json::to_writer(&mut *writer, &record)
+            .map_err(std::io::Error::from)
+            .and_then(|_| writer.write_all(b"\n"))
+            .and_then(|_| writer.flush());
+        if let Err(e) = result {
+            tracing::error!("Failed to write recording {}: {}", path.display(), e);
+            self.writer = None;
+            self.failed = true;
+        }
+    }
+}
@@ -0,0 +1,254 @@
+//! Labels remembered across frames.
+//!
+//! The game re-issues every draw call each frame, so anything that outlives a single frame
+//! (fades, hold timers, duration caps) needs to recognise a label it has seen before. The map is
+//! capped and evicts the least recently seen label, so a game emitting endless unique strings
+//! can't grow it without bound.
+
+use std::{
+    collections::{BTreeMap, HashMap, hash_map::DefaultHasher},
+    hash::{Hash, Hasher},
+    time::{Duration, Instant},
+};
+
+use crate::pipeline::ResolvedText;
+
+/// A label that goes unseen for longer than this counts as a new appearance when it returns.
+const SIGHTING_GAP: Duration = Duration::from_millis(250);
+/// How long a label takes to fade out once it exceeds its maximum display duration.
+const DURATION_CAP_FADE: Duration = Duration::from_millis(500);
+/// Share of the hold duration over which a held label fades out.
+const HOLD_FADE_FRACTION: f32 = 0.25;
+
+#[derive(Debug)]
+pub struct RetainedLabel {
+    /// Most recent sighting of the label.
+    pub label: ResolvedText,
+    pub first_seen: Instant,
+    pub last_seen: Instant,
+    /// Position in the recency order; higher was seen more recently.
+    tick: u64,
+}
+
+#[derive(Debug)]
+pub struct RetainedLabels {
+    capacity: usize,
+    entries: HashMap<u64, RetainedLabel>,
+    /// Recency tick to key, oldest first.
+    recency: BTreeMap<u64, u64>,
+    next_tick: u64,
+}
+
+/// Alpha multiplier for a label that has been on screen continuously for `age`.
+///
+/// Fully opaque until `max_duration`, then fades out over [`DURATION_CAP_FADE`].
+pub fn duration_cap_alpha(age: Duration, max_duration: Duration) -> f32 {
+    let Some(overtime) = age.checked_sub(max_duration) else {
+        return 1.0;
+    };
+    (1.0 - overtime.as_secs_f32() / DURATION_CAP_FADE.as_secs_f32()).max(0.0)
+}
+
+/// Alpha multiplier for a label the game stopped drawing `since` ago.
+///
+/// With a `fade_out`, the label stays opaque for `hold` and then fades out over `fade_out`.
+/// Without one it fades out over the last [`HOLD_FADE_FRACTION`] of `hold` instead.
+pub fn linger_alpha(since: Duration, hold: Duration, fade_out: Option<Duration>) -> f32 {
+    let (visible, fade) = match fade_out {
+        Some(fade_out) => (hold + fade_out, fade_out),
+        None => (hold, hold.mul_f32(HOLD_FADE_FRACTION)),
+    };
+
+    let Some(remaining) = visible.checked_sub(since) else {
+        return 0.0;
+    };
+    if fade.is_zero() || remaining >= fade {
+        return 1.0;
+    }
+    remaining.as_secs_f32() / fade.as_secs_f32()
+}
+
+/// Alpha multiplier for a label that has been on screen for `age`, fading in over `fade_in`.
+pub fn fade_in_alpha(age: Duration, fade_in: Duration) -> f32 {
+    (age.as_secs_f32() / fade_in.as_secs_f32()).min(1.0)
+}
+
+/// Identifies a label across frames by its text and coordinate mode.
+///
+/// The position is left out so text that moves, like nameplates or anything in world space
+/// while the camera turns, stays the same label. Copies of one string drawn in the same mode
+/// share an entry.
+pub fn label_key(label: &ResolvedText) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    label.text.hash(&mut hasher);
+    label.mode.hash(&mut hasher);
+    hasher.finish()
+}
+
+impl RetainedLabels {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: HashMap::new(),
+            recency: BTreeMap::new(),
+            next_tick: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        self.evict_to_capacity();
+    }
+
+    /// Records a sighting of `label` at `now`, evicting the least recently seen label if full.
+    pub fn observe(&mut self, key: u64, label: &ResolvedText, now: Instant) {
+        let tick = self.next_tick;
+        self.next_tick += 1;
+
+        match self.entries.get_mut(&key) {
+            Some(entry) => {
+                self.recency.remove(&entry.tick);
+                entry.label.clone_from(label);
+                if now.duration_since(entry.last_seen) > SIGHTING_GAP {
+                    entry.first_seen = now;
+                }
+                entry.last_seen = now;
+                entry.tick = tick;
+            }
+            None => {
+                self.entries.insert(
+                    key,
+                    RetainedLabel {
+                        label: label.clone(),
+                        first_seen: now,
+                        last_seen: now,
+                        tick,
+                    },
+                );
+            }
+        }
+        self.recency.insert(tick, key);
+        self.evict_to_capacity();
+    }
+
+    pub fn get(&self, key: u64) -> Option<&RetainedLabel> {
+        self.entries.get(&key)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (u64, &RetainedLabel)> {
+        self.entries.iter().map(|(&key, entry)| (key, entry))
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.recency.clear();
+    }
+
+    fn evict_to_capacity(&mut self) {
+        while self.entries.len() > self.capacity {
+            let Some((_, key)) = self.recency.pop_first() else {
+                break;
+            };
+            self.entries.remove(&key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn label(text: &str) -> ResolvedText {
+        ResolvedText {
+            text: text.to_owned(),
+            mode: None,
+            raw_pos: (0.0, 0.0, 0.0),
+            screen_pos: (0.0, 0.0),
+            depth: None,
+            color: [1.0; 4],
+            font_size: 24.0,
+        }
+    }
+
+    #[test]
+    fn evicts_least_recently_seen_under_churn() {
+        let mut retained = RetainedLabels::new(8);
+        let now = Instant::now();
+        let keys: Vec<u64> = (0..100)
+            .map(|i| {
+                let label = label(&format!("label {i}"));
+                let key = label_key(&label);
+                retained.observe(key, &label, now);
+                key
+            })
+            .collect();
+
+        assert_eq!(retained.len(), 8);
+        for (i, &key) in keys.iter().enumerate() {
+            assert_eq!(retained.get(key).is_some(), i >= 92, "label {i}");
+        }
+    }
+
+    #[test]
+    fn seeing_a_label_again_keeps_it_from_eviction() {
+        let mut retained = RetainedLabels::new(2);
+        let now = Instant::now();
+        let [first, second, third] = ["first", "second", "third"].map(label);
+        let [first_key, second_key, third_key] = [&first, &second, &third].map(label_key);
+
+        retained.observe(first_key, &first, now);
+        retained.observe(second_key, &second, now);
+        retained.observe(first_key, &first, now);
+        retained.observe(third_key, &third, now);
+
+        assert!(retained.get(first_key).is_some());
+        assert!(retained.get(second_key).is_none());
+        assert!(retained.get(third_key).is_some());
+    }
+
+    /// Observes a label moving to the right over 60 frames at 60 fps, returning its key.
+    fn observe_moving(retained: &mut RetainedLabels, start: Instant) -> u64 {
+        let mut moving = label("nameplate");
+        let mut key = 0;
+        for frame in 0..60 {
+            moving.raw_pos = (frame as f32, 0.0, 10.0);
+            moving.screen_pos = (frame as f32 * 4.0, 540.0);
+            key = label_key(&moving);
+            retained.observe(key, &moving, start + Duration::from_millis(frame * 16));
+        }
+        key
+    }
+
+    #[test]
+    fn moving_label_fades_in() {
+        let mut retained = RetainedLabels::new(8);
+        let key = observe_moving(&mut retained, Instant::now());
+
+        let entry = retained.get(key).unwrap();
+        let age = entry.last_seen.duration_since(entry.first_seen);
+        assert_eq!(age, Duration::from_millis(59 * 16));
+        assert_eq!(fade_in_alpha(age, Duration::from_millis(500)), 1.0);
+    }
+
+    #[test]
+    fn moving_label_leaves_one_copy_and_reaches_its_duration_cap() {
+        let mut retained = RetainedLabels::new(8);
+        let key = observe_moving(&mut retained, Instant::now());
+
+        // Only the last position lingers once the game stops drawing it
+        assert_eq!(retained.len(), 1);
+        let entry = retained.get(key).unwrap();
+        assert_eq!(entry.label.raw_pos, (59.0, 0.0, 10.0));
+
+        let age = entry.last_seen.duration_since(entry.first_seen);
+        assert_eq!(duration_cap_alpha(age, Duration::from_millis(200)), 0.0);
+    }
+}
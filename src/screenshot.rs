@@ -0,0 +1,164 @@
+//! Saving what the game window shows, overlay included, as a PNG.
+//!
+//! hudhook doesn't hand the render loop the swap chain, so the back buffer can't be read back
+//! directly. Screenshots are copied from the desktop instead, which only sees the game when it
+//! is composited like any other window.
+
+use std::{
+    fs::File,
+    io::{self, BufWriter},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use windows::Win32::{
+    Foundation::{HWND, POINT, RECT},
+    Graphics::Gdi::{
+        BI_RGB, BITMAPINFO, BITMAPINFOHEADER, BitBlt, ClientToScreen, CreateCompatibleBitmap,
+        CreateCompatibleDC, DIB_RGB_COLORS, DeleteDC, DeleteObject, GetDC, GetDIBits, ReleaseDC,
+        SRCCOPY, SelectObject,
+    },
+    UI::WindowsAndMessaging::GetClientRect,
+};
+
+/// Pixels of a captured window, top row first.
+pub struct Screenshot {
+    width: u32,
+    height: u32,
+    /// 8-bit RGBA.
+    pixels: Vec<u8>,
+}
+
+impl Screenshot {
+    /// Copies the client area of `window` as currently composited on screen, or returns `None`
+    /// if that fails or comes back all black.
+    ///
+    /// Anything covering the window is captured along with it. In exclusive fullscreen, or
+    /// whenever the swap chain presents without going through the desktop compositor, the
+    /// desktop holds a black or stale image rather than the game.
+    pub fn capture(window: HWND) -> Option<Self> {
+        let mut client = RECT::default();
+        unsafe { GetClientRect(window, &mut client) }
+            .inspect_err(|e| tracing::error!("Failed to get the game window's size: {}", e))
+            .ok()?;
+        let mut origin = POINT::default();
+        if !unsafe { ClientToScreen(window, &mut origin) }.as_bool() {
+            tracing::error!("Failed to locate the game window on screen");
+            return None;
+        }
+        let (width, height) = (client.right - client.left, client.bottom - client.top);
+        if width <= 0 || height <= 0 {
+            return None;
+        }
+
+        let mut pixels = vec![0; width as usize * height as usize * 4];
+        let mut info = BITMAPINFO {
+            bmiHeader: BITMAPINFOHEADER {
+                biSize: size_of::<BITMAPINFOHEADER>() as u32,
+                biWidth: width,
+                // Negative for rows top to bottom
+                biHeight: -height,
+                biPlanes: 1,
+                biBitCount: 32,
+                biCompression: BI_RGB.0,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let copied = unsafe {
+            let screen = GetDC(HWND(0));
+            let memory = CreateCompatibleDC(screen);
+            let bitmap = CreateCompatibleBitmap(screen, width, height);
+            let previous = SelectObject(memory, bitmap);
+            let blitted = BitBlt(
+                memory, 0, 0, width, height, screen, origin.x, origin.y, SRCCOPY,
+            );
+            SelectObject(memory, previous);
+            let lines = GetDIBits(
+                memory,
+                bitmap,
+                0,
+                height as u32,
+                Some(pixels.as_mut_ptr().cast()),
+                &mut info,
+                DIB_RGB_COLORS,
+            );
+            DeleteObject(bitmap);
+            DeleteDC(memory);
+            ReleaseDC(HWND(0), screen);
+            blitted.is_ok() && lines == height
+        };
+        if !copied {
+            tracing::error!("Failed to copy the game window for a screenshot");
+            return None;
+        }
+
+        if is_all_black(&pixels) {
+            tracing::warn!(
+                "Screenshot of the game window came back all black, not saving it. The game \
+                 is probably not composited by the desktop, try borderless windowed mode"
+            );
+            return None;
+        }
+
+        // GDI hands out BGRX
+        for pixel in pixels.chunks_exact_mut(4) {
+            pixel.swap(0, 2);
+            pixel[3] = u8::MAX;
+        }
+        Some(Self {
+            width: width as u32,
+            height: height as u32,
+            pixels,
+        })
+    }
+
+    /// Encodes and writes the screenshot to a timestamped PNG in `dir` on a thread of its own,
+    /// so the frame isn't held up by it.
+    pub fn save_in_background(self, dir: PathBuf) {
+        std::thread::spawn(move || match self.save(&dir) {
+            Ok(path) => tracing::info!("Saved screenshot to {}", path.display()),
+            Err(e) => tracing::error!("Failed to save screenshot in {}: {}", dir.display(), e),
+        });
+    }
+
+    fn save(&self, dir: &Path) -> io::Result<PathBuf> {
+        std::fs::create_dir_all(dir)?;
+        let timestamp = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let path = dir.join(format!("textrender-{timestamp}.png"));
+
+        let mut encoder = png::Encoder::new(
+            BufWriter::new(File::create(&path)?),
+            self.width,
+            self.height,
+        );
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder
+            .write_header()
+            .and_then(|mut writer| writer.write_image_data(&self.pixels))
+            .map_err(io::Error::other)?;
+        Ok(path)
+    }
+}
+
+/// Whether every pixel of a BGRX image is black, ignoring the unused fourth byte.
+fn is_all_black(pixels: &[u8]) -> bool {
+    pixels
+        .chunks_exact(4)
+        .all(|pixel| pixel[..3].iter().all(|&channel| channel == 0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_black_images_count_as_all_black() {
+        assert!(is_all_black(&[0, 0, 0, 0, 0, 0, 0, 255]));
+        assert!(!is_all_black(&[0, 0, 0, 255, 0, 1, 0, 255]));
+    }
+}
@@ -0,0 +1,200 @@
+use std::path::PathBuf;
+
+use hudhook::imgui::{self, Ui};
+
+use crate::command::CoordMode;
+use crate::config::{
+    self, FontSettings, MAX_OVERSAMPLE, MAX_TEXT_SCALE, MIN_OVERSAMPLE, MIN_TEXT_SCALE,
+};
+use crate::metrics::Phase;
+use crate::projection::CAMERA_COUNT;
+
+/// Read-only numbers shown in the stats section of the menu.
+#[derive(Debug, Default)]
+pub struct OverlayStats {
+    /// Size of the currently built font atlas.
+    pub atlas_size: Option<(u32, u32)>,
+    pub retained_labels: usize,
+    pub retained_label_capacity: usize,
+    pub queue_len: usize,
+    /// Capacity the queue was created with, which may differ from the configured one.
+    pub queue_capacity: usize,
+    pub dropped_commands: u64,
+    /// Average milliseconds per [`Phase`] of the render loop, while profiling is enabled.
+    pub phase_ms: Option<[f32; Phase::ALL.len()]>,
+}
+
+/// In-game settings window, toggled with Insert and hidden by default.
+pub struct SettingsMenu {
+    open: bool,
+    /// Font settings being edited; only take effect once applied.
+    font: FontSettings,
+    font_path: String,
+}
+
+impl SettingsMenu {
+    pub fn new() -> Self {
+        let font = config::current().font.clone();
+        Self {
+            open: false,
+            font_path: font.path.display().to_string(),
+            font,
+        }
+    }
+
+    /// Draws the menu if it is open.
+    ///
+    /// Returns the font settings to rebuild the atlas with when the user applies them.
+    pub fn draw(&mut self, ui: &Ui, stats: &OverlayStats) -> Option<FontSettings> {
+        if ui.is_key_pressed(imgui::Key::Insert) {
+            self.open = !self.open;
+            if self.open {
+                self.font = config::current().font.clone();
+                self.font_path = self.font.path.display().to_string();
+            }
+        }
+        if !self.open {
+            return None;
+        }
+
+        let mut rebuild = None;
+        ui.window("Text Render Settings")
+            .opened(&mut self.open)
+            .always_auto_resize(true)
+            .collapsible(false)
+            .build(|| {
+                ui.text("Font");
+                ui.separator();
+                ui.input_text("Path", &mut self.font_path).build();
+                ui.input_scalar("Face (.ttc)", &mut self.font.face_index)
+                    .step(1)
+                    .build();
+                ui.slider("Base size (px)", 8.0, 96.0, &mut self.font.size_px);
+                ui.slider(
+                    "Oversample H",
+                    MIN_OVERSAMPLE,
+                    MAX_OVERSAMPLE,
+                    &mut self.font.oversample_h,
+                );
+                ui.slider(
+                    "Oversample V",
+                    MIN_OVERSAMPLE,
+                    MAX_OVERSAMPLE,
+                    &mut self.font.oversample_v,
+                );
+                ui.checkbox("Pixel snap H", &mut self.font.pixel_snap_h);
+                ui.checkbox("Korean (Hangul)", &mut self.font.hangul);
+                ui.checkbox("Right-to-left (Hebrew, Arabic)", &mut self.font.rtl);
+
+                if ui.button("Apply & Rebuild Atlas") {
+                    self.font.path = PathBuf::from(&self.font_path);
+                    let font = self.font.clone();
+                    config::update(|config| config.font = font.clone());
+                    rebuild = Some(font);
+                }
+
+                match stats.atlas_size {
+                    Some((width, height)) => ui.text(format!("Atlas: {width}x{height}")),
+                    None => ui.text("Atlas: not built"),
+                }
+
+                ui.spacing();
+                ui.text("Style");
+                ui.separator();
+                Self::draw_style(ui);
+
+                ui.spacing();
+                ui.text("Coordinate modes");
+                ui.separator();
+                Self::draw_modes(ui);
+
+                ui.spacing();
+                ui.text("Stats");
+                ui.separator();
+                ui.text(format!(
+                    "Retained labels: {}/{}",
+                    stats.retained_labels, stats.retained_label_capacity
+                ));
+                ui.text(format!(
+                    "Queue: {}/{}",
+                    stats.queue_len, stats.queue_capacity
+                ));
+                ui.text_disabled("Set queue_capacity in textrender.toml, read at startup");
+                ui.text(format!("Dropped commands: {}", stats.dropped_commands));
+
+                if let Some(phase_ms) = stats.phase_ms {
+                    ui.spacing();
+                    ui.text("Render loop (avg ms)");
+                    ui.separator();
+                    for (phase, ms) in Phase::ALL.iter().zip(phase_ms) {
+                        ui.text(format!("{phase:?}: {ms:.3}"));
+                    }
+                }
+            });
+
+        rebuild
+    }
+
+    /// Checkboxes for which coordinate modes are drawn and the camera world-space text is
+    /// projected with, applied as soon as they are changed.
+    fn draw_modes(ui: &Ui) {
+        let config = config::current();
+
+        for mode in CoordMode::ALL {
+            let mut enabled = config.enabled_modes.contains(&mode);
+            if ui.checkbox(format!("{mode:?}"), &mut enabled) {
+                config::update(|config| {
+                    if enabled {
+                        config.enabled_modes.insert(mode);
+                    } else {
+                        config.enabled_modes.remove(&mode);
+                    }
+                });
+            }
+        }
+
+        let mut max_distance = config.max_render_distance;
+        if ui.slider("Max distance (m, 0 = off)", 0.0, 500.0, &mut max_distance) {
+            config::update(|config| config.max_render_distance = max_distance);
+        }
+
+        // Photo mode and some cutscenes project through another camera
+        let mut camera_index = config.camera_index;
+        if ui.slider("Camera", 1, CAMERA_COUNT, &mut camera_index) {
+            config::update(|config| config.camera_index = camera_index);
+        }
+    }
+
+    /// Sliders that apply to the config as soon as they are moved.
+    fn draw_style(ui: &Ui) {
+        let config = config::current();
+
+        let mut scale = config.text_scale_factor();
+        if ui.slider("Text scale", MIN_TEXT_SCALE, MAX_TEXT_SCALE, &mut scale) {
+            config::update(|config| config.text_scale = scale);
+        }
+
+        let mut dpi_multiplier = config.dpi_scale_multiplier;
+        if ui.slider("DPI multiplier", 0.25, 4.0, &mut dpi_multiplier) {
+            config::update(|config| config.dpi_scale_multiplier = dpi_multiplier);
+        }
+
+        let mut tabular_digits = config.tabular_digits;
+        if ui.checkbox("Tabular digits", &mut tabular_digits) {
+            config::update(|config| config.tabular_digits = tabular_digits);
+        }
+
+        let mut thickness = config.outline.thickness;
+        if ui.slider("Outline thickness", 0.0, 8.0, &mut thickness) {
+            config::update(|config| config.outline.thickness = thickness);
+        }
+
+        let mut offset = config.shadow.offset;
+        if ui
+            .slider_config("Shadow offset", -8.0, 8.0)
+            .build_array(&mut offset)
+        {
+            config::update(|config| config.shadow.offset = offset);
+        }
+    }
+}
@@ -0,0 +1,174 @@
+//! Publishes intercepted text into a named shared memory ring buffer, for readers that can't
+//! afford a socket or pipe round trip per event.
+//!
+//! The segment starts with a 64 byte header followed by [`SLOT_COUNT`] slots of [`SLOT_SIZE`]
+//! bytes, all little-endian. Writers never wait for readers: each record claims the next slot
+//! and overwrites whatever was in it, and a per-slot sequence number lets readers notice records
+//! that changed under them. The layout is spelled out for consumers in the README.
+
+use std::{
+    sync::{
+        OnceLock,
+        atomic::{AtomicU64, Ordering, fence},
+    },
+    time::SystemTime,
+};
+
+use windows::{
+    Win32::{
+        Foundation::{CloseHandle, INVALID_HANDLE_VALUE},
+        System::Memory::{
+            CreateFileMappingW, FILE_MAP_ALL_ACCESS, MapViewOfFile, PAGE_READWRITE, UnmapViewOfFile,
+        },
+    },
+    core::PCWSTR,
+};
+
+use crate::command::{CoordMode, DrawCommand};
+
+/// `TRSM` read as a little-endian u32, at the very start of the segment.
+const MAGIC: u32 = u32::from_le_bytes(*b"TRSM");
+const VERSION: u32 = 1;
+const SLOT_COUNT: u32 = 4096;
+const SLOT_SIZE: usize = 256;
+const HEADER_SIZE: usize = 64;
+/// Offset of the number of records ever written within the header.
+const WRITE_INDEX_OFFSET: usize = 16;
+/// Offset of the UTF-8 text within a slot, after its fixed fields.
+const TEXT_OFFSET: usize = 40;
+/// Mode byte of text drawn directly in screen pixels.
+const NO_MODE: u8 = u8::MAX;
+
+static RING: OnceLock<Ring> = OnceLock::new();
+
+/// The mapped segment.
+struct Ring {
+    base: *mut u8,
+}
+
+// The view stays mapped for the life of the process, and everything written to it concurrently
+// is claimed through atomics first
+unsafe impl Send for Ring {}
+unsafe impl Sync for Ring {}
+
+impl Ring {
+    fn write_index(&self) -> &AtomicU64 {
+        unsafe { &*self.base.add(WRITE_INDEX_OFFSET).cast::<AtomicU64>() }
+    }
+
+    fn write(&self, text: &str, (x, y, z): (f32, f32, f32), mode: Option<CoordMode>) {
+        let index = self.write_index().fetch_add(1, Ordering::AcqRel);
+        let slot_index = (index % u64::from(SLOT_COUNT)) as usize;
+        let slot = unsafe { self.base.add(HEADER_SIZE + slot_index * SLOT_SIZE) };
+        let sequence = unsafe { &*slot.cast::<AtomicU64>() };
+
+        // Odd while the record is being written, so readers know to retry
+        sequence.store(index * 2 + 1, Ordering::Relaxed);
+        fence(Ordering::Release);
+
+        let text = truncate_utf8(text, SLOT_SIZE - TEXT_OFFSET);
+        let mode = mode
+            .and_then(|mode| CoordMode::ALL.iter().position(|&known| known == mode))
+            .map_or(NO_MODE, |position| position as u8);
+        let ts = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs_f64();
+        unsafe {
+            slot.add(8).write(mode);
+            slot.add(12).cast::<u32>().write(text.len() as u32);
+            slot.add(16).cast::<f32>().write(x);
+            slot.add(20).cast::<f32>().write(y);
+            slot.add(24).cast::<f32>().write(z);
+            slot.add(32).cast::<f64>().write(ts);
+            std::ptr::copy_nonoverlapping(text.as_ptr(), slot.add(TEXT_OFFSET), text.len());
+        }
+
+        sequence.store(index * 2 + 2, Ordering::Release);
+    }
+}
+
+/// Creates or opens the shared memory segment `name` and starts publishing into it.
+///
+/// `name` is a kernel object name, e.g. `Local\textrender`.
+pub fn start(name: &str) {
+    if RING.get().is_some() {
+        tracing::warn!("Already publishing to shared memory, ignoring {}", name);
+        return;
+    }
+
+    let size = HEADER_SIZE + SLOT_COUNT as usize * SLOT_SIZE;
+    let mut wide_name: Vec<u16> = name.encode_utf16().collect();
+    wide_name.push(0);
+
+    let mapping = match unsafe {
+        CreateFileMappingW(
+            INVALID_HANDLE_VALUE,
+            None,
+            PAGE_READWRITE,
+            0,
+            size as u32,
+            PCWSTR(wide_name.as_ptr()),
+        )
+    } {
+        Ok(mapping) => mapping,
+        Err(e) => {
+            tracing::error!("Failed to create shared memory {}: {:?}", name, e);
+            return;
+        }
+    };
+    // The mapping handle is never closed, so the segment lives as long as the game
+    let view = unsafe { MapViewOfFile(mapping, FILE_MAP_ALL_ACCESS, 0, 0, size) };
+    if view.Value.is_null() {
+        tracing::error!(
+            "Failed to map shared memory {}: {:?}",
+            name,
+            windows::core::Error::from_win32()
+        );
+        let _ = unsafe { CloseHandle(mapping) };
+        return;
+    }
+
+    let base = view.Value.cast::<u8>();
+    // Lost a race with another call, whose segment readers may already be using
+    if RING.set(Ring { base }).is_err() {
+        tracing::warn!("Already publishing to shared memory, ignoring {}", name);
+        unsafe {
+            let _ = UnmapViewOfFile(view);
+            let _ = CloseHandle(mapping);
+        }
+        return;
+    }
+
+    // Only once this segment is the one being published to. A reader may have created it
+    // first, so the write index is left alone
+    unsafe {
+        base.cast::<u32>().write(MAGIC);
+        base.add(4).cast::<u32>().write(VERSION);
+        base.add(8).cast::<u32>().write(SLOT_COUNT);
+        base.add(12).cast::<u32>().write(SLOT_SIZE as u32);
+    }
+    tracing::info!("Publishing text events to shared memory {}", name);
+}
+
+/// Writes `command` into the ring buffer. Does nothing unless [`start`] succeeded.
+pub fn publish(command: &DrawCommand) {
+    let Some(ring) = RING.get() else {
+        return;
+    };
+
+    match command {
+        DrawCommand::Text(text, x, y, z, mode, _) => ring.write(text, (*x, *y, *z), Some(*mode)),
+        DrawCommand::ScreenText(text, x, y) => ring.write(text, (*x, *y, 0.0), None),
+        DrawCommand::Line(..) | DrawCommand::Rect(..) => {}
+    }
+}
+
+/// The longest prefix of `text` that fits in `max_bytes` without splitting a character.
+fn truncate_utf8(text: &str, max_bytes: usize) -> &str {
+    let mut end = text.len().min(max_bytes);
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    &text[..end]
+}
@@ -0,0 +1,216 @@
+//! Locates the hooked game functions.
+//!
+//! Offsets shift with every game patch. On a game version listed in [`KNOWN_VERSIONS`] its
+//! offsets are used as is. Any other version has each function searched for by byte signature
+//! in the executable's code, falling back to the offsets of the newest known version.
+//!
+//! A pattern only replaces those offsets once it is marked [`Signature::verified`], meaning it
+//! was checked to match exactly once, at the function's offset in the table, in the executable
+//! of a known version. Neither pattern has been checked yet, so for now the scan only logs
+//! whether it agrees with the table.
+
+use std::fmt;
+
+use pelite::{image::IMAGE_SCN_MEM_EXECUTE, pattern, pe::Pe};
+
+/// A hooked function and how to find it.
+pub struct Signature {
+    pub name: &'static str,
+    /// IDA-style byte pattern covering the function's prologue; `?` matches any byte.
+    pub pattern: &'static str,
+    /// Whether `pattern` has been checked against a known game version, so that a match can be
+    /// trusted over the offsets of another version.
+    pub verified: bool,
+}
+
+// void CS::CSEzDraw::DrawText(CSEzDraw *param_1,FloatVector4 *param_2,wchar_t *param_3)
+pub const DRAW_TEXT: Signature = Signature {
+    name: "CSEzDraw::DrawText",
+    pattern: "48 89 5C 24 ? 57 48 83 EC ? 49 8B D8 48 8B FA 48 85 C9 74 ? 48 8B 41",
+    verified: false,
+};
+
+// void CS::CSEzDraw::DrawTextWithOffset(CSEzDraw *param_1,FloatVector4 *param_2,float (*offset) [2],wchar_t *param_4)
+pub const DRAW_TEXT_WITH_OFFSET: Signature = Signature {
+    name: "CSEzDraw::DrawTextWithOffset",
+    pattern: "48 89 5C 24 ? 48 89 74 24 ? 57 48 83 EC ? 49 8B F1 49 8B D8 48 8B FA",
+    verified: false,
+};
+
+/// File version of the game executable, as shown in its properties.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GameVersion(pub [u16; 4]);
+
+impl fmt::Display for GameVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [major, minor, patch, build] = self.0;
+        write!(f, "{major}.{minor}.{patch}.{build}")
+    }
+}
+
+/// Function offsets for one game version.
+pub struct VersionRvas {
+    pub version: GameVersion,
+    pub draw_text: u32,
+    pub draw_text_with_offset: u32,
+}
+
+/// Offsets of every game version the overlay has been checked against, oldest first.
+pub static KNOWN_VERSIONS: &[VersionRvas] = &[VersionRvas {
+    version: GameVersion([2, 6, 1, 0]),
+    draw_text: 0x264efc0,
+    draw_text_with_offset: 0x264ef20,
+}];
+
+/// Reads the file version from the executable's version resource.
+pub fn detect_version<'a>(program: impl Pe<'a>) -> Option<GameVersion> {
+    let resources = program
+        .resources()
+        .inspect_err(|e| tracing::warn!("Failed to read the game's resources: {}", e))
+        .ok()?;
+    let version_info = resources
+        .version_info()
+        .inspect_err(|e| tracing::warn!("Failed to read the game's version info: {}", e))
+        .ok()?;
+    let version = version_info.fixed()?.dwFileVersion;
+
+    Some(GameVersion([
+        version.Major,
+        version.Minor,
+        version.Patch,
+        version.Build,
+    ]))
+}
+
+/// Offsets for `version` and whether they are that version's own, or those of the newest known
+/// version if it isn't in the table.
+pub fn rvas_for(version: Option<GameVersion>) -> (&'static VersionRvas, bool) {
+    let newest = KNOWN_VERSIONS.last().expect("no known game versions");
+    let Some(version) = version else {
+        tracing::warn!(
+            "Couldn't detect the game version, assuming offsets of {}",
+            newest.version
+        );
+        return (newest, false);
+    };
+
+    match KNOWN_VERSIONS.iter().find(|known| known.version == version) {
+        Some(known) => {
+            tracing::info!("Detected game version {}", version);
+            (known, true)
+        }
+        None => {
+            tracing::warn!(
+                "Game version {} is not supported yet, please file an issue with this version. \
+                 Assuming offsets of {}",
+                version,
+                newest.version
+            );
+            (newest, false)
+        }
+    }
+}
+
+/// Returns the RVA of `signature` in `program`'s code, or `fallback_rva` if the pattern doesn't
+/// match exactly once.
+///
+/// A pattern that matches more than once is as good as no match, since hooking the wrong
+/// function would corrupt the game rather than just miss text. A match that disagrees with
+/// `fallback_rva` is only used if the signature is verified, and logged either way, since one
+/// of the two is out of date.
+pub fn locate<'a>(program: impl Pe<'a>, signature: &Signature, fallback_rva: u32) -> u32 {
+    let pattern = match pattern::parse(signature.pattern) {
+        Ok(pattern) => pattern,
+        Err(e) => {
+            tracing::error!("Invalid pattern for {}: {}", signature.name, e);
+            return fallback(signature, fallback_rva, "invalid pattern");
+        }
+    };
+
+    let scanner = program.scanner();
+    let mut matches = scanner.matches_code(&pattern);
+    let mut save = [0; 1];
+    if !matches.next(&mut save) {
+        return fallback(signature, fallback_rva, "pattern not found");
+    }
+    let rva = save[0];
+    if matches.next(&mut save) {
+        return fallback(signature, fallback_rva, "pattern is ambiguous");
+    }
+
+    if rva == fallback_rva {
+        tracing::info!("Found {} by pattern scan at RVA {:#x}", signature.name, rva);
+        return rva;
+    }
+    tracing::warn!(
+        "Found {} by pattern scan at RVA {:#x}, but the version table has {:#x}",
+        signature.name,
+        rva,
+        fallback_rva
+    );
+    if !signature.verified {
+        return fallback(signature, fallback_rva, "pattern is unverified");
+    }
+    rva
+}
+
+/// Returns the virtual address of `signature`, or `None` if its RVA doesn't point into one of
+/// the executable's code sections.
+///
+/// `table_rva` comes from [`rvas_for`]. It is used as is when it is the running version's own
+/// (`exact`), and otherwise only as the fallback of [`locate`].
+///
+/// Detouring an address outside of code would crash the game on the first call rather than
+/// just miss text, so such a hook is better skipped.
+pub fn resolve<'a>(
+    program: impl Pe<'a>,
+    signature: &Signature,
+    table_rva: u32,
+    exact: bool,
+) -> Option<u64> {
+    let rva = if exact {
+        tracing::info!(
+            "Using version table RVA {:#x} for {}",
+            table_rva,
+            signature.name
+        );
+        table_rva
+    } else {
+        locate(program, signature, table_rva)
+    };
+
+    let in_code = program.section_headers().iter().any(|section| {
+        section.Characteristics & IMAGE_SCN_MEM_EXECUTE != 0
+            && section.virtual_range().contains(&rva)
+    });
+    if !in_code {
+        tracing::error!(
+            "RVA {:#x} for {} is outside the game's code, not hooking it",
+            rva,
+            signature.name
+        );
+        return None;
+    }
+
+    program
+        .rva_to_va(rva)
+        .inspect_err(|e| {
+            tracing::error!(
+                "RVA {:#x} for {} is invalid, not hooking it: {}",
+                rva,
+                signature.name,
+                e
+            )
+        })
+        .ok()
+}
+
+fn fallback(signature: &Signature, rva: u32, reason: &str) -> u32 {
+    tracing::warn!(
+        "Using version table RVA {:#x} for {} ({})",
+        rva,
+        signature.name,
+        reason
+    );
+    rva
+}
@@ -0,0 +1,161 @@
+//! Decorations drawn behind label text to keep it readable over busy backgrounds, and the
+//! debug shapes drawn alongside it.
+
+use hudhook::imgui::{Ui, sys};
+
+use crate::config::{BackgroundSettings, OutlineSettings, ShadowSettings};
+use crate::pipeline::{ResolvedShape, ShapeKind};
+
+/// Offsets the outline is stamped at, scaled by the thickness.
+const OUTLINE_DIRECTIONS: [[f32; 2]; 8] = [
+    [-1.0, -1.0],
+    [0.0, -1.0],
+    [1.0, -1.0],
+    [-1.0, 0.0],
+    [1.0, 0.0],
+    [-1.0, 1.0],
+    [0.0, 1.0],
+    [1.0, 1.0],
+];
+
+/// Horizontal shift per pixel of height that slants synthetic italics, about 11 degrees.
+const ITALIC_SHEAR: f32 = 0.2;
+
+/// Synthetic styling for fonts without bold or italic faces of their own.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Emphasis {
+    /// Text is drawn a second time one pixel to the right.
+    pub bold: bool,
+    /// Text is slanted to the right.
+    pub italic: bool,
+}
+
+/// Draws a drop shadow of `text` at screen position `pos` into the current window's draw list.
+///
+/// The offset is scaled by `font_scale` so the shadow keeps its proportions at any text size.
+pub fn draw_shadow(
+    ui: &Ui,
+    pos: [f32; 2],
+    text: &str,
+    shadow: &ShadowSettings,
+    font_scale: f32,
+    alpha: f32,
+) {
+    if !shadow.enabled {
+        return;
+    }
+
+    let mut color = shadow.color.0;
+    color[3] *= alpha;
+
+    ui.get_window_draw_list().add_text(
+        [
+            pos[0] + shadow.offset[0] * font_scale,
+            pos[1] + shadow.offset[1] * font_scale,
+        ],
+        color,
+        text,
+    );
+}
+
+/// Draws an outline around `text` at screen position `pos` into the current window's draw list,
+/// so text submitted afterwards lands on top. `alpha` is the label's own opacity.
+///
+/// The thickness is multiplied by `scale`, the user's text scale.
+pub fn draw_outline(
+    ui: &Ui,
+    pos: [f32; 2],
+    text: &str,
+    outline: &OutlineSettings,
+    scale: f32,
+    alpha: f32,
+) {
+    if outline.thickness <= 0.0 {
+        return;
+    }
+    let thickness = outline.thickness * scale;
+
+    let mut color = outline.color.0;
+    color[3] *= alpha;
+
+    let draw_list = ui.get_window_draw_list();
+    for [dx, dy] in OUTLINE_DIRECTIONS {
+        draw_list.add_text(
+            [pos[0] + dx * thickness, pos[1] + dy * thickness],
+            color,
+            text,
+        );
+    }
+}
+
+/// Draws a box around the text bounds `min`..`max` into the current window's draw list,
+/// so text submitted afterwards lands on top. `alpha` is the label's own opacity.
+///
+/// Padding and rounding are multiplied by `scale`, the user's text scale.
+pub fn draw_background(
+    ui: &Ui,
+    [min, max]: [[f32; 2]; 2],
+    background: &BackgroundSettings,
+    scale: f32,
+    alpha: f32,
+) {
+    if !background.enabled {
+        return;
+    }
+
+    let mut color = background.color.0;
+    color[3] *= alpha;
+
+    let [pad_x, pad_y] = background.padding.map(|padding| padding * scale);
+    ui.get_window_draw_list()
+        .add_rect(
+            [min[0] - pad_x, min[1] - pad_y],
+            [max[0] + pad_x, max[1] + pad_y],
+            color,
+        )
+        .filled(true)
+        .rounding(background.rounding * scale)
+        .build();
+}
+
+/// Width in pixels of lines and rectangle outlines.
+const SHAPE_THICKNESS: f32 = 1.0;
+
+/// Draws `shape` into the current window's draw list.
+pub fn draw_shape(ui: &Ui, shape: &ResolvedShape) {
+    let from = [shape.from.0, shape.from.1];
+    let to = [shape.to.0, shape.to.1];
+
+    let draw_list = ui.get_window_draw_list();
+    match shape.kind {
+        ShapeKind::Line => draw_list
+            .add_line(from, to, shape.color)
+            .thickness(SHAPE_THICKNESS)
+            .build(),
+        ShapeKind::Rect { filled } => {
+            // ImGui expects the top-left corner first
+            let min = [from[0].min(to[0]), from[1].min(to[1])];
+            let max = [from[0].max(to[0]), from[1].max(to[1])];
+            draw_list
+                .add_rect(min, max, shape.color)
+                .filled(filled)
+                .thickness(SHAPE_THICKNESS)
+                .build()
+        }
+    }
+}
+
+/// Runs `draw`, then slants whatever it added to the current window's draw list to the right
+/// like italic text, pivoting around the horizontal line at screen y `baseline`.
+pub fn draw_slanted(baseline: f32, draw: impl FnOnce()) {
+    // imgui-rs has no access to the vertices of a draw list, so go through the raw one
+    let draw_list = unsafe { sys::igGetWindowDrawList() };
+    let start = unsafe { (*draw_list).VtxBuffer.Size };
+    draw();
+
+    let vertices = unsafe { (*draw_list).VtxBuffer };
+    for index in start..vertices.Size {
+        let vertex = unsafe { &mut *vertices.Data.add(index as usize) };
+        vertex.pos.x += (baseline - vertex.pos.y) * ITALIC_SHEAR;
+    }
+}
@@ -0,0 +1,327 @@
+use std::{collections::HashMap, str::FromStr};
+
+use regex::Regex;
+use serde::{Deserialize, Deserializer};
+use unicode_normalization::{UnicodeNormalization, char::is_combining_mark};
+
+use crate::pool;
+
+/// Appended to text cut short by [`truncate`]. Plain ASCII so it's always in the font atlas.
+const TRUNCATION_MARKER: &str = "...";
+
+/// Splits `text` into the pieces that have to stay together when it is broken up for layout:
+/// a character with any combining marks, variation selectors and emoji modifiers after it, and
+/// characters joined with a zero width joiner.
+///
+/// A close enough approximation of grapheme clusters for game text, without pulling in the
+/// full segmentation rules.
+pub fn clusters(text: &str) -> impl Iterator<Item = &str> {
+    let mut rest = text;
+    std::iter::from_fn(move || {
+        let mut chars = rest.char_indices().peekable();
+        let (_, first) = chars.next()?;
+        let mut joined = first == ZERO_WIDTH_JOINER;
+        let mut end = rest.len();
+        while let Some(&(index, c)) = chars.peek() {
+            if !joined && !extends_cluster(c) {
+                end = index;
+                break;
+            }
+            joined = c == ZERO_WIDTH_JOINER;
+            chars.next();
+        }
+
+        let (cluster, tail) = rest.split_at(end);
+        rest = tail;
+        Some(cluster)
+    })
+}
+
+const ZERO_WIDTH_JOINER: char = '\u{200D}';
+
+/// Whether `c` attaches to the character before it rather than standing on its own.
+fn extends_cluster(c: char) -> bool {
+    is_combining_mark(c)
+        || matches!(c,
+            ZERO_WIDTH_JOINER
+            | '\u{FE00}'..='\u{FE0F}' // Variation selectors
+            | '\u{1F3FB}'..='\u{1F3FF}' // Emoji skin tone modifiers
+            | '\u{E0100}'..='\u{E01EF}') // Variation selectors supplement
+}
+
+/// Cuts `text` down to at most `max_chars` characters, marking it as truncated.
+///
+/// Pathologically long strings would otherwise produce enormous windows and make every
+/// frame pay for measuring and laying out thousands of glyphs. `max_chars == 0` disables the cap.
+pub fn truncate(mut text: String, max_chars: usize) -> String {
+    if max_chars == 0 {
+        return text;
+    }
+
+    // Cut between clusters so no combining mark loses its base character
+    let mut end = 0;
+    let mut chars = 0;
+    for cluster in clusters(&text) {
+        chars += cluster.chars().count();
+        if chars > max_chars {
+            break;
+        }
+        end += cluster.len();
+    }
+    if end < text.len() {
+        text.truncate(end);
+        text.push_str(TRUNCATION_MARKER);
+    }
+    text
+}
+
+/// Whether `c` belongs to a right-to-left script.
+fn is_rtl_char(c: char) -> bool {
+    matches!(c,
+        '\u{0590}'..='\u{08FF}' // Hebrew, Arabic, Syriac, Thaana, NKo, ...
+        | '\u{FB1D}'..='\u{FDFF}' // Hebrew and Arabic presentation forms
+        | '\u{FE70}'..='\u{FEFF}')
+}
+
+/// Whether `c` is a letter or digit that keeps its left-to-right order inside right-to-left text.
+fn is_ltr_char(c: char) -> bool {
+    c.is_alphanumeric() && !is_rtl_char(c)
+}
+
+/// Whether `text` has more right-to-left letters than left-to-right ones.
+pub fn is_rtl(text: &str) -> bool {
+    let mut balance = 0i32;
+    for c in text.chars() {
+        if is_rtl_char(c) {
+            balance += 1;
+        } else if c.is_alphabetic() {
+            balance -= 1;
+        }
+    }
+    balance > 0
+}
+
+/// Reorders every line of right-to-left `text` from logical into visual order, so it reads
+/// correctly when drawn left to right.
+///
+/// This is a simplification of the Unicode bidi algorithm: embedded runs of left-to-right
+/// letters and digits keep their order, everything else is mirrored. Arabic letters aren't
+/// shaped, so they show in their isolated forms.
+pub fn rtl_visual_order(text: &str) -> String {
+    let mut visual = String::with_capacity(text.len());
+    for (index, line) in text.split('\n').enumerate() {
+        if index > 0 {
+            visual.push('\n');
+        }
+
+        let chars: Vec<char> = line.chars().collect();
+        let mut end = chars.len();
+        while end > 0 {
+            if is_ltr_char(chars[end - 1]) {
+                let mut start = end - 1;
+                while start > 0
+                    && (is_ltr_char(chars[start - 1]) || is_ltr_joiner(&chars, start - 1))
+                {
+                    start -= 1;
+                }
+                visual.extend(&chars[start..end]);
+                end = start;
+            } else {
+                visual.push(chars[end - 1]);
+                end -= 1;
+            }
+        }
+    }
+    visual
+}
+
+/// Whether the punctuation at `index` sits between two left-to-right characters, like the
+/// point in `1.5` or the hyphen in `x-ray`, and belongs to their run.
+fn is_ltr_joiner(chars: &[char], index: usize) -> bool {
+    matches!(chars[index], '.' | ',' | ':' | '-' | '/')
+        && index > 0
+        && index + 1 < chars.len()
+        && is_ltr_char(chars[index - 1])
+        && is_ltr_char(chars[index + 1])
+}
+
+/// Case conversion applied to captured text before it is drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TextCase {
+    #[default]
+    None,
+    Upper,
+    Lower,
+}
+
+impl FromStr for TextCase {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "none" => Ok(Self::None),
+            "upper" => Ok(Self::Upper),
+            "lower" => Ok(Self::Lower),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Applies the configured case conversion and, if `nfc` is set, NFC normalization.
+///
+/// NFC composes decomposed sequences (e.g. a base letter followed by a combining accent)
+/// into single code points, which the font renders without misplaced marks.
+pub fn transform(text: String, case: TextCase, nfc: bool) -> String {
+    let text = if nfc { text.nfc().collect() } else { text };
+
+    match case {
+        TextCase::None => text,
+        TextCase::Upper => text.to_uppercase(),
+        TextCase::Lower => text.to_lowercase(),
+    }
+}
+
+/// Regexes matched against captured text, written as a list of strings in settings.
+///
+/// Compiled once when the settings are loaded. Patterns that fail to compile are logged and
+/// left out instead of failing the whole config.
+#[derive(Debug, Clone, Default)]
+pub struct TextPatterns(Vec<Regex>);
+
+impl TextPatterns {
+    pub fn is_match(&self, text: &str) -> bool {
+        self.0.iter().any(|pattern| pattern.is_match(text))
+    }
+}
+
+impl<'de> Deserialize<'de> for TextPatterns {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let patterns = Vec::<String>::deserialize(deserializer)?;
+        Ok(Self(
+            patterns
+                .iter()
+                .filter_map(|pattern| {
+                    Regex::new(pattern)
+                        .inspect_err(|e| {
+                            tracing::error!("Skipping invalid text pattern {:?}: {}", pattern, e)
+                        })
+                        .ok()
+                })
+                .collect(),
+        ))
+    }
+}
+
+/// Replacements applied to captured text, e.g. to translate debug strings.
+#[derive(Debug, Clone, Default)]
+pub struct Substitutions {
+    /// Whole strings replaced verbatim.
+    exact: HashMap<String, String>,
+    /// Tried in order when no exact entry matches; the first matching regex rewrites the text.
+    patterns: Vec<(Regex, String)>,
+}
+
+impl Substitutions {
+    /// Returns `text` rewritten by the first matching rule, or unchanged if none match.
+    ///
+    /// Pattern replacements can refer to capture groups as `$1` or `${name}`.
+    pub fn apply(&self, mut text: String) -> String {
+        // Written into the buffer passed in, so exact matches don't allocate
+        if let Some(replacement) = self.exact.get(&text) {
+            text.clear();
+            text.push_str(replacement);
+            return text;
+        }
+
+        match self
+            .patterns
+            .iter()
+            .find(|(pattern, _)| pattern.is_match(&text))
+        {
+            Some((pattern, replacement)) => {
+                let rewritten = pattern
+                    .replace_all(&text, replacement.as_str())
+                    .into_owned();
+                pool::recycle(text);
+                rewritten
+            }
+            None => text,
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Substitutions {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        struct PatternRule {
+            pattern: String,
+            replace: String,
+        }
+
+        #[derive(Deserialize, Default)]
+        #[serde(default)]
+        struct Rules {
+            exact: HashMap<String, String>,
+            patterns: Vec<PatternRule>,
+        }
+
+        let rules = Rules::deserialize(deserializer)?;
+        let patterns = rules
+            .patterns
+            .into_iter()
+            .filter_map(|rule| {
+                Regex::new(&rule.pattern)
+                    .inspect_err(|e| {
+                        tracing::error!("Skipping invalid substitution {:?}: {}", rule.pattern, e)
+                    })
+                    .ok()
+                    .map(|pattern| (pattern, rule.replace))
+            })
+            .collect();
+
+        Ok(Self {
+            exact: rules.exact,
+            patterns,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn supplementary_plane_characters_stay_whole() {
+        // U+20000 and U+2A6D6 from the Supplementary Ideographic Plane, a combining mark after
+        // the first
+        let text = "\u{20000}\u{0301}\u{2A6D6}";
+        assert_eq!(
+            clusters(text).collect::<Vec<_>>(),
+            ["\u{20000}\u{0301}", "\u{2A6D6}"]
+        );
+        assert_eq!(truncate(text.to_owned(), 2), "\u{20000}\u{0301}...");
+    }
+
+    #[test]
+    fn long_text_is_cut_to_the_limit() {
+        let text = truncate("a".repeat(10_000), 1024);
+        assert_eq!(text.len(), 1024 + TRUNCATION_MARKER.len());
+        assert!(text.ends_with(TRUNCATION_MARKER));
+    }
+
+    #[test]
+    fn exact_substitution_reuses_the_buffer() {
+        let substitutions = Substitutions {
+            exact: HashMap::from([("DEBUG".to_owned(), "Debug".to_owned())]),
+            ..Substitutions::default()
+        };
+        let mut text = String::with_capacity(64);
+        text.push_str("DEBUG");
+        let buffer = text.as_ptr();
+
+        let text = substitutions.apply(text);
+        assert_eq!(text, "Debug");
+        assert_eq!(text.as_ptr(), buffer);
+    }
+}